@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS songs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR(250) NOT NULL,
+    artist VARCHAR(250) NOT NULL,
+    genre VARCHAR(250) NOT NULL,
+    play_count INTEGER DEFAULT 0
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS artist_summaries (
+    artist VARCHAR(250) PRIMARY KEY,
+    song_count INTEGER NOT NULL,
+    total_plays INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS genre_summaries (
+    genre VARCHAR(250) PRIMARY KEY,
+    song_count INTEGER NOT NULL,
+    total_plays INTEGER NOT NULL
+);
@@ -0,0 +1,21 @@
+-- SQLite cannot add a column with a non-constant default, so rebuild the
+-- table to give new rows a creation-time updated_at.
+CREATE TABLE songs_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR(250) NOT NULL,
+    artist VARCHAR(250) NOT NULL,
+    genre VARCHAR(250) NOT NULL,
+    play_count INTEGER DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+INSERT INTO songs_new (id, title, artist, genre, play_count)
+SELECT id, title, artist, genre, play_count FROM songs;
+
+DELETE FROM sqlite_sequence WHERE name = 'songs_new';
+INSERT INTO sqlite_sequence (name, seq)
+SELECT 'songs_new', seq FROM sqlite_sequence WHERE name = 'songs';
+
+DROP TABLE songs;
+
+ALTER TABLE songs_new RENAME TO songs;
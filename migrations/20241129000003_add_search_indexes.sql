@@ -0,0 +1,8 @@
+-- LIKE is case-insensitive, so the text indexes use NOCASE to stay usable
+-- for prefix matches (LIKE 'value%') and exact COLLATE NOCASE comparisons.
+-- Substring matches (LIKE '%value%') still scan the table.
+CREATE INDEX IF NOT EXISTS idx_songs_title ON songs (title COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS idx_songs_artist ON songs (artist COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS idx_songs_genre ON songs (genre COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS idx_songs_play_count ON songs (play_count);
+CREATE INDEX IF NOT EXISTS idx_songs_genre_artist ON songs (genre COLLATE NOCASE, artist COLLATE NOCASE);
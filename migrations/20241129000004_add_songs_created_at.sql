@@ -0,0 +1,29 @@
+-- Rebuild again so created_at gets a default, like updated_at. Existing
+-- rows have no real creation time, so they take their updated_at.
+CREATE TABLE songs_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title VARCHAR(250) NOT NULL,
+    artist VARCHAR(250) NOT NULL,
+    genre VARCHAR(250) NOT NULL,
+    play_count INTEGER DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+INSERT INTO songs_new (id, title, artist, genre, play_count, updated_at, created_at)
+SELECT id, title, artist, genre, play_count, updated_at, updated_at FROM songs;
+
+DELETE FROM sqlite_sequence WHERE name = 'songs_new';
+INSERT INTO sqlite_sequence (name, seq)
+SELECT 'songs_new', seq FROM sqlite_sequence WHERE name = 'songs';
+
+DROP TABLE songs;
+
+ALTER TABLE songs_new RENAME TO songs;
+
+CREATE INDEX idx_songs_title ON songs (title COLLATE NOCASE);
+CREATE INDEX idx_songs_artist ON songs (artist COLLATE NOCASE);
+CREATE INDEX idx_songs_genre ON songs (genre COLLATE NOCASE);
+CREATE INDEX idx_songs_play_count ON songs (play_count);
+CREATE INDEX idx_songs_genre_artist ON songs (genre COLLATE NOCASE, artist COLLATE NOCASE);
+CREATE INDEX idx_songs_created_at ON songs (created_at);
@@ -0,0 +1,26 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS songs_fts USING fts5 (
+    title,
+    artist,
+    genre,
+    content = 'songs',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER IF NOT EXISTS songs_fts_insert AFTER INSERT ON songs BEGIN
+    INSERT INTO songs_fts (rowid, title, artist, genre)
+    VALUES (new.id, new.title, new.artist, new.genre);
+END;
+
+CREATE TRIGGER IF NOT EXISTS songs_fts_delete AFTER DELETE ON songs BEGIN
+    INSERT INTO songs_fts (songs_fts, rowid, title, artist, genre)
+    VALUES ('delete', old.id, old.title, old.artist, old.genre);
+END;
+
+CREATE TRIGGER IF NOT EXISTS songs_fts_update AFTER UPDATE OF title, artist, genre ON songs BEGIN
+    INSERT INTO songs_fts (songs_fts, rowid, title, artist, genre)
+    VALUES ('delete', old.id, old.title, old.artist, old.genre);
+    INSERT INTO songs_fts (rowid, title, artist, genre)
+    VALUES (new.id, new.title, new.artist, new.genre);
+END;
+
+INSERT INTO songs_fts (songs_fts) VALUES ('rebuild');
@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN duration_seconds INTEGER CHECK (duration_seconds > 0);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS plays (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    song_id INTEGER NOT NULL REFERENCES songs (id) ON DELETE CASCADE,
+    played_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_plays_played_at ON plays (played_at);
+CREATE INDEX IF NOT EXISTS idx_plays_song_id ON plays (song_id, id);
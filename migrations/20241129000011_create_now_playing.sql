@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS now_playing (
+    slot INTEGER PRIMARY KEY NOT NULL CHECK (slot = 1),
+    song_id INTEGER REFERENCES songs (id) ON DELETE SET NULL,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+INSERT OR IGNORE INTO now_playing (slot) VALUES (1);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS plays_archive (
+    id INTEGER PRIMARY KEY NOT NULL,
+    song_id INTEGER NOT NULL REFERENCES songs (id) ON DELETE CASCADE,
+    played_at TEXT NOT NULL,
+    archived_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
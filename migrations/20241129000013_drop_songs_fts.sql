@@ -0,0 +1,6 @@
+-- Search still runs on LIKE over the songs indexes, so the FTS5 table only
+-- cost a write per insert and update. It can return once search uses it.
+DROP TRIGGER IF EXISTS songs_fts_insert;
+DROP TRIGGER IF EXISTS songs_fts_delete;
+DROP TRIGGER IF EXISTS songs_fts_update;
+DROP TABLE IF EXISTS songs_fts;
@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Path, State},
+    middleware::from_fn_with_state,
+    routing::{get, post},
+    Json,
+};
+use serde::Serialize;
+
+use crate::{
+    db,
+    error::AppError,
+    events::PlayEvent,
+    middleware,
+    routes::Routes,
+    search_stats::SearchStatsReport,
+    song_id::SongId,
+    summaries::{self, RecomputeResult},
+    AppState, Song,
+};
+
+#[derive(Serialize)]
+struct PlaysReset {
+    cleared: u64,
+}
+
+pub fn routes(state: AppState) -> Routes {
+    Routes::new()
+        .route("/recompute", post(recompute_summaries))
+        .route("/search-stats", get(search_stats))
+        .route("/plays/reset", post(reset_plays))
+        .route("/songs/:a/swap-plays/:b", post(swap_plays))
+        .map(|router| router.route_layer(from_fn_with_state(state, middleware::require_admin)))
+}
+
+async fn recompute_summaries(
+    State(state): State<AppState>,
+) -> Result<Json<RecomputeResult>, AppError> {
+    let result = db::timed(
+        state.config.slow_query_ms,
+        "recompute_summaries",
+        summaries::recompute(&state.db),
+    )
+    .await?;
+
+    Ok(Json(result))
+}
+
+// Plays are moved to plays_archive rather than deleted, keeping their
+// original ids, so the history can still be recovered. play_count on
+// songs is lifetime and is left as it is.
+async fn reset_plays(State(state): State<AppState>) -> Result<Json<PlaysReset>, AppError> {
+    let cleared = db::timed(
+        state.config.slow_query_ms,
+        "reset_plays",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                sqlx::query(
+                    "INSERT INTO plays_archive (id, song_id, played_at)
+                    SELECT id, song_id, played_at FROM plays",
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                let result = sqlx::query("DELETE FROM plays").execute(&mut *tx).await?;
+                Ok(result.rows_affected())
+            })
+        }),
+    )
+    .await?;
+
+    Ok(Json(PlaysReset { cleared }))
+}
+
+async fn search_stats(State(state): State<AppState>) -> Json<SearchStatsReport> {
+    Json(state.search_stats.report())
+}
+
+async fn swap_plays(
+    State(state): State<AppState>,
+    Path((a, b)): Path<(String, String)>,
+) -> Result<Json<[Song; 2]>, AppError> {
+    let a: SongId = a.parse()?;
+    let b: SongId = b.parse()?;
+    if a == b {
+        return Err(AppError::bad_request(
+            "Cannot swap a song's plays with itself",
+        ));
+    }
+
+    let songs = db::timed(
+        state.config.slow_query_ms,
+        "swap_plays",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let counts: Vec<(SongId, i64)> =
+                    sqlx::query_as("SELECT id, play_count FROM songs WHERE id IN (?, ?)")
+                        .bind(a)
+                        .bind(b)
+                        .fetch_all(&mut *tx)
+                        .await?;
+
+                let count_of = |id: SongId| {
+                    counts
+                        .iter()
+                        .find(|(song_id, _)| *song_id == id)
+                        .map(|(_, play_count)| *play_count)
+                        .ok_or_else(|| AppError::not_found(format!("Song {} not found", id)))
+                };
+                let (plays_a, plays_b) = (count_of(a)?, count_of(b)?);
+
+                let mut songs = sqlx::query_as::<_, Song>(
+                    "UPDATE songs SET play_count = CASE id WHEN ? THEN ? ELSE ? END,
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+                    WHERE id IN (?, ?) RETURNING *",
+                )
+                .bind(a)
+                .bind(plays_b)
+                .bind(plays_a)
+                .bind(a)
+                .bind(b)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                songs.sort_by_key(|song| song.id != a.0);
+                let second = songs.pop().expect("both songs were updated");
+                let first = songs.pop().expect("both songs were updated");
+                Ok([first, second])
+            })
+        }),
+    )
+    .await?;
+
+    for song in &songs {
+        state.events.publish_play(PlayEvent {
+            song_id: SongId(song.id),
+            play_count: song.play_count,
+        });
+    }
+
+    Ok(Json(songs))
+}
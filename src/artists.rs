@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db,
+    error::AppError,
+    pagination::{self, PagedSongs, Pagination},
+    search::escape_like,
+    text,
+    version::ApiVersion,
+    AppState, Song,
+};
+
+const DEFAULT_SUGGEST_LIMIT: i64 = 10;
+const MAX_SUGGEST_LIMIT: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct SuggestParams {
+    q: String,
+    limit: Option<i64>,
+}
+
+pub async fn suggest_artists(
+    State(state): State<AppState>,
+    Query(params): Query<SuggestParams>,
+) -> Result<Json<Vec<String>>, AppError> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SUGGEST_LIMIT)
+        .clamp(1, MAX_SUGGEST_LIMIT);
+
+    let artists: Vec<(String,)> = db::timed(
+        state.config.slow_query_ms,
+        "suggest_artists",
+        sqlx::query_as(
+            "SELECT artist FROM songs WHERE artist LIKE ? ESCAPE '\\'
+            GROUP BY artist COLLATE NOCASE ORDER BY COUNT(*) DESC, artist LIMIT ?",
+        )
+        .bind(format!(
+            "{}%",
+            escape_like(&text::normalize(params.q.trim()))
+        ))
+        .bind(limit)
+        .fetch_all(&state.db),
+    )
+    .await?;
+
+    Ok(Json(artists.into_iter().map(|(artist,)| artist).collect()))
+}
+
+// Songs are not grouped by album because the schema has no album column;
+// the totals cover every song by the artist, while songs is paginated: a
+// bare array for v1 and PagedSongs for v2.
+#[derive(Serialize)]
+pub struct ArtistSongs<T> {
+    artist: String,
+    song_count: i64,
+    total_plays: i64,
+    songs: T,
+}
+
+// An artist with no songs is a 404 rather than an empty page, matching
+// how GET /songs/:id treats a missing song.
+pub async fn artist_songs(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let name = text::normalize(name.trim());
+    let requested = name.clone();
+    let limit = page.limit(state.config.max_page_size);
+    let offset = page.offset();
+
+    let ((song_count, total_plays), songs) = db::timed(
+        state.config.slow_query_ms,
+        "artist_songs",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let totals: (i64, i64) = sqlx::query_as(
+                    "SELECT COUNT(*), COALESCE(SUM(play_count), 0) FROM songs
+                    WHERE artist = ? COLLATE NOCASE",
+                )
+                .bind(&name)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                if totals.0 == 0 {
+                    return Err(AppError::not_found("Artist not found"));
+                }
+
+                let songs = sqlx::query_as::<_, Song>(
+                    "SELECT * FROM songs WHERE artist = ? COLLATE NOCASE
+                    ORDER BY play_count DESC, title COLLATE NOCASE, id LIMIT ? OFFSET ?",
+                )
+                .bind(&name)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                Ok((totals, songs))
+            })
+        }),
+    )
+    .await?;
+
+    let artist = songs
+        .first()
+        .map(|song| song.artist.clone())
+        .unwrap_or(requested);
+    let headers = pagination::headers(limit, song_count);
+
+    Ok(match version {
+        ApiVersion::V1 => (
+            version,
+            headers,
+            Json(ArtistSongs {
+                artist,
+                song_count,
+                total_plays,
+                songs,
+            }),
+        )
+            .into_response(),
+        ApiVersion::V2 => (
+            version,
+            headers,
+            Json(ArtistSongs {
+                artist,
+                song_count,
+                total_plays,
+                songs: PagedSongs {
+                    items: songs,
+                    total: song_count,
+                    limit,
+                    offset,
+                },
+            }),
+        )
+            .into_response(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct ArtistStats {
+    artist: String,
+    song_count: i64,
+    total_plays: i64,
+    average_plays: f64,
+    most_played: Song,
+    genres: Vec<String>,
+}
+
+pub async fn artist_stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ArtistStats>, AppError> {
+    let name = &text::normalize(name.trim());
+
+    let (song_count, total_plays, average_plays): (i64, i64, f64) = db::timed(
+        state.config.slow_query_ms,
+        "artist_stats",
+        sqlx::query_as(
+            "SELECT COUNT(*), COALESCE(SUM(play_count), 0), COALESCE(AVG(play_count), 0.0)
+            FROM songs WHERE artist = ? COLLATE NOCASE",
+        )
+        .bind(name)
+        .fetch_one(&state.db),
+    )
+    .await?;
+
+    if song_count == 0 {
+        return Err(AppError::not_found("Artist not found"));
+    }
+
+    let most_played = db::timed(
+        state.config.slow_query_ms,
+        "artist_most_played",
+        sqlx::query_as::<_, Song>(
+            "SELECT * FROM songs WHERE artist = ? COLLATE NOCASE
+            ORDER BY play_count DESC, id LIMIT 1",
+        )
+        .bind(name)
+        .fetch_one(&state.db),
+    )
+    .await?;
+
+    let genres: Vec<(String,)> = db::timed(
+        state.config.slow_query_ms,
+        "artist_genres",
+        sqlx::query_as(
+            "SELECT genre FROM songs WHERE artist = ? COLLATE NOCASE
+            GROUP BY genre COLLATE NOCASE ORDER BY genre COLLATE NOCASE",
+        )
+        .bind(name)
+        .fetch_all(&state.db),
+    )
+    .await?;
+
+    Ok(Json(ArtistStats {
+        artist: most_played.artist.clone(),
+        song_count,
+        total_plays,
+        average_plays,
+        most_played,
+        genres: genres.into_iter().map(|(genre,)| genre).collect(),
+    }))
+}
@@ -0,0 +1,187 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::QueryBuilder;
+
+use crate::{
+    db, error::AppError, search::SearchParams, song_id::SongId, tags, text, AppState, Song,
+};
+
+const DRY_RUN_SAMPLE_SIZE: i64 = 10;
+
+#[derive(Deserialize)]
+pub struct DryRunParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct Recategorize {
+    from_genre: String,
+    to_genre: String,
+}
+
+#[derive(Deserialize)]
+pub struct BulkTag {
+    filter: SearchParams,
+    tag: String,
+}
+
+#[derive(Serialize)]
+pub struct BulkTagResult {
+    tagged: u64,
+}
+
+#[derive(Serialize)]
+pub struct RowValidation {
+    index: usize,
+    valid: bool,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct BulkResult {
+    updated: u64,
+    dry_run: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sample_ids: Vec<SongId>,
+}
+
+pub async fn recategorize(
+    State(state): State<AppState>,
+    Query(params): Query<DryRunParams>,
+    Json(payload): Json<Recategorize>,
+) -> Result<Json<BulkResult>, AppError> {
+    let from_genre = &text::normalize(payload.from_genre.trim());
+    let to_genre = &text::normalize(payload.to_genre.trim());
+
+    if from_genre.is_empty() || to_genre.is_empty() {
+        return Err(AppError::bad_request(
+            "from_genre and to_genre must not be empty",
+        ));
+    }
+
+    if params.dry_run {
+        let (count,): (i64,) = db::timed(
+            state.config.slow_query_ms,
+            "recategorize_dry_run",
+            sqlx::query_as("SELECT COUNT(*) FROM songs WHERE genre = ? COLLATE NOCASE")
+                .bind(from_genre)
+                .fetch_one(&state.db),
+        )
+        .await?;
+
+        let sample_ids: Vec<(SongId,)> = sqlx::query_as(
+            "SELECT id FROM songs WHERE genre = ? COLLATE NOCASE ORDER BY id LIMIT ?",
+        )
+        .bind(from_genre)
+        .bind(DRY_RUN_SAMPLE_SIZE)
+        .fetch_all(&state.db)
+        .await?;
+
+        return Ok(Json(BulkResult {
+            updated: count as u64,
+            dry_run: true,
+            sample_ids: sample_ids.into_iter().map(|(id,)| id).collect(),
+        }));
+    }
+
+    let result = db::timed(
+        state.config.slow_query_ms,
+        "recategorize",
+        sqlx::query(
+            "UPDATE songs SET genre = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+            WHERE genre = ? COLLATE NOCASE",
+        )
+        .bind(to_genre)
+        .bind(from_genre)
+        .execute(&state.db),
+    )
+    .await?;
+
+    Ok(Json(BulkResult {
+        updated: result.rows_affected(),
+        dry_run: false,
+        sample_ids: Vec::new(),
+    }))
+}
+
+// Songs that already carry the tag are left alone, so tagged counts only
+// the songs this request changed.
+pub async fn tag_matching(
+    State(state): State<AppState>,
+    Json(payload): Json<BulkTag>,
+) -> Result<Json<BulkTagResult>, AppError> {
+    payload.filter.validate(state.config.max_search_filters)?;
+    if !payload.filter.has_filter() {
+        return Err(AppError::bad_request(
+            "filter must narrow the songs to tag; refusing to tag the whole library",
+        ));
+    }
+    let tag = tags::normalize(&payload.tag)?;
+
+    let tagged = db::timed(
+        state.config.slow_query_ms,
+        "tag_matching",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO tags (name) VALUES (?) ON CONFLICT (name) DO NOTHING")
+                    .bind(&tag)
+                    .execute(&mut *tx)
+                    .await?;
+
+                let mut touch = QueryBuilder::new(
+                    "UPDATE songs SET updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')",
+                );
+                payload.filter.push_where(&mut touch);
+                touch.push(
+                    " AND id NOT IN (SELECT song_tags.song_id FROM song_tags
+                    JOIN tags ON tags.id = song_tags.tag_id WHERE tags.name = ",
+                );
+                touch.push_bind(&tag);
+                touch.push(")");
+                touch.build().execute(&mut *tx).await?;
+
+                let mut insert = QueryBuilder::new(
+                    "INSERT OR IGNORE INTO song_tags (song_id, tag_id)
+                    SELECT id, (SELECT tags.id FROM tags WHERE tags.name = ",
+                );
+                insert.push_bind(&tag);
+                insert.push(") FROM songs");
+                payload.filter.push_where(&mut insert);
+                let result = insert.build().execute(&mut *tx).await?;
+
+                Ok(result.rows_affected())
+            })
+        }),
+    )
+    .await?;
+
+    Ok(Json(BulkTagResult { tagged }))
+}
+
+// Runs the checks POST /songs/bulk applies, row by row, and writes
+// nothing. Rows are decoded one at a time so a malformed row is reported
+// against its index instead of rejecting the whole body.
+pub async fn validate_songs(Json(payload): Json<Vec<Value>>) -> Json<Vec<RowValidation>> {
+    let rows = payload
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let errors = match serde_json::from_value::<Song>(row) {
+                Ok(song) => song.validation_errors(),
+                Err(error) => vec![error.to_string()],
+            };
+            RowValidation {
+                index,
+                valid: errors.is_empty(),
+                errors,
+            }
+        })
+        .collect();
+
+    Json(rows)
+}
@@ -0,0 +1,61 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+
+use crate::AppState;
+
+// The address of the client behind the request. X-Forwarded-For and
+// X-Real-IP are only believed when the socket peer is one of the proxies
+// listed in TRUSTED_PROXY; from anyone else they are ignored, since any
+// client can set them.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or(IpAddr::from([0, 0, 0, 0]), |ConnectInfo(addr)| addr.ip());
+
+        let trusted = &state.config.trusted_proxies;
+        if !trusted.contains(&peer) {
+            return Ok(Self(peer));
+        }
+
+        Ok(Self(
+            forwarded_for(&parts.headers, trusted)
+                .or_else(|| real_ip(&parts.headers))
+                .unwrap_or(peer),
+        ))
+    }
+}
+
+// Each proxy appends the address it received the request from, so the
+// list is walked from the right and the first hop that is not one of our
+// own proxies is the client.
+fn forwarded_for(headers: &HeaderMap, trusted: &[IpAddr]) -> Option<IpAddr> {
+    let hops = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().parse::<IpAddr>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    hops.into_iter().rev().find(|hop| !trusted.contains(hop))
+}
+
+fn real_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers.get("x-real-ip")?.to_str().ok()?.trim().parse().ok()
+}
@@ -0,0 +1,112 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+use crate::Song;
+
+pub async fn library_last_modified(db: &SqlitePool) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let (updated_at,): (Option<String>,) = sqlx::query_as("SELECT MAX(updated_at) FROM songs")
+        .fetch_one(db)
+        .await?;
+
+    Ok(updated_at
+        .and_then(|updated_at| DateTime::parse_from_rfc3339(&updated_at).ok())
+        .map(|updated_at| updated_at.with_timezone(&Utc)))
+}
+
+pub fn not_modified_since(headers: &HeaderMap, last_modified: DateTime<Utc>) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| last_modified <= since)
+}
+
+pub fn http_date(timestamp: DateTime<Utc>) -> HeaderValue {
+    HeaderValue::from_str(&timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        .expect("HTTP date is a valid header value")
+}
+
+// A strong ETag over every stored column of the song, so any write that
+// changes the row, including a play or a tag bump to updated_at, changes
+// the tag. A ?fields= projection is a different representation, so its
+// field list is hashed too. FNV-1a keeps it stable across builds and Rust
+// releases.
+pub fn song_etag(song: &Song, fields: Option<&[String]>) -> String {
+    let duration = song
+        .duration_seconds
+        .map_or(String::new(), |duration| duration.to_string());
+    let columns = [
+        song.id.to_string(),
+        song.title.clone(),
+        song.artist.clone(),
+        song.genre.clone(),
+        song.play_count.to_string(),
+        duration,
+        song.updated_at.clone(),
+        song.created_at.clone(),
+    ];
+    let projection = fields.map(|fields| format!("fields={}", fields.join(",")));
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in columns.iter().chain(&projection) {
+        for byte in field.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    format!("\"{:016x}\"", hash)
+}
+
+// Strong comparison per RFC 9110: * matches any current representation and
+// weak tags never match. A request without If-Match is unconditional.
+pub fn if_match(headers: &HeaderMap, etag: &str) -> bool {
+    let mut values = headers
+        .get_all(header::IF_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .peekable();
+
+    if values.peek().is_none() {
+        return true;
+    }
+
+    values.any(|value| value == "*" || value == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song() -> Song {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "title": "Hey Jude",
+            "artist": "The Beatles",
+            "genre": "Rock",
+            "play_count": 3,
+            "updated_at": "2024-11-29T00:00:00Z",
+            "created_at": "2024-11-29T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn projections_get_their_own_etag() {
+        let song = song();
+        let title = ["title".to_string()];
+
+        assert_ne!(song_etag(&song, None), song_etag(&song, Some(&title)));
+    }
+
+    #[test]
+    fn a_play_changes_the_etag() {
+        let mut played = song();
+        played.play_count += 1;
+
+        assert_ne!(song_etag(&song(), None), song_etag(&played, None));
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    env, fmt,
+    io::{self, IsTerminal},
+    net::IpAddr,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    #[default]
+    Safe,
+    Fast,
+}
+
+impl Durability {
+    pub fn pragmas(self) -> &'static str {
+        match self {
+            Durability::Safe => "PRAGMA journal_mode = WAL; PRAGMA synchronous = FULL;",
+            Durability::Fast => "PRAGMA journal_mode = MEMORY; PRAGMA synchronous = OFF;",
+        }
+    }
+}
+
+impl FromStr for Durability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "safe" => Ok(Durability::Safe),
+            "fast" => Ok(Durability::Fast),
+            _ => Err(format!("Unknown durability profile: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Durability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Durability::Safe => "safe",
+            Durability::Fast => "fast",
+        })
+    }
+}
+
+// Human-readable logs at an interactive terminal, JSON lines otherwise
+// so log aggregators can ingest them without LOG_FORMAT being set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl LogFormat {
+    fn detect() -> Self {
+        if io::stdout().is_terminal() {
+            LogFormat::Pretty
+        } else {
+            LogFormat::Json
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub database_url: String,
+    pub max_query_length: usize,
+    pub summary_interval_secs: u64,
+    pub max_songs: i64,
+    pub slow_query_ms: u64,
+    pub play_event_window_ms: u64,
+    pub cors_expose_headers: Vec<String>,
+    pub db_durability: Durability,
+    pub enable_visit_counter: bool,
+    pub max_page_size: i64,
+    pub admin_token: Option<String>,
+    pub request_timeout_ms: u64,
+    pub log_format: LogFormat,
+    pub max_concurrent_requests: usize,
+    pub idempotency_key_ttl_secs: u64,
+    pub suggest_routes: bool,
+    pub trending_window_days: u64,
+    pub max_search_filters: usize,
+    pub trusted_proxies: Vec<IpAddr>,
+    pub chaos_mode: bool,
+    pub chaos_fail_percent: u64,
+    pub chaos_delay_percent: u64,
+    pub chaos_delay_ms: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            database_url: env_or("DATABASE_URL", "sqlite://songs.db".to_string()),
+            max_query_length: env_or("MAX_QUERY_LENGTH", 2048),
+            summary_interval_secs: env_or("SUMMARY_INTERVAL_SECS", 0),
+            max_songs: env_or("MAX_SONGS", 0),
+            slow_query_ms: env_or("SLOW_QUERY_MS", 500),
+            play_event_window_ms: env_or("PLAY_EVENT_WINDOW_MS", 250),
+            cors_expose_headers: env_list(
+                "CORS_EXPOSE_HEADERS",
+                &[
+                    "x-total-count",
+                    "x-page-limit",
+                    "x-api-version",
+                    "link",
+                    "location",
+                    "etag",
+                    "x-request-id",
+                ],
+            ),
+            db_durability: env_or("DB_DURABILITY", Durability::default()),
+            enable_visit_counter: env_or("ENABLE_VISIT_COUNTER", false),
+            max_page_size: env_or("MAX_PAGE_SIZE", 100).max(1),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            request_timeout_ms: env_or("REQUEST_TIMEOUT_MS", 30_000),
+            log_format: env_or("LOG_FORMAT", LogFormat::detect()),
+            max_concurrent_requests: env_or("MAX_CONCURRENT_REQUESTS", 0),
+            idempotency_key_ttl_secs: env_or("IDEMPOTENCY_KEY_TTL_SECS", 86_400),
+            suggest_routes: env_or("SUGGEST_ROUTES", true),
+            trending_window_days: env_or("TRENDING_WINDOW_DAYS", 7).max(1),
+            max_search_filters: env_or("MAX_SEARCH_FILTERS", 50),
+            trusted_proxies: env_list("TRUSTED_PROXY", &[])
+                .iter()
+                .map(|proxy| proxy.parse().expect("Invalid address in TRUSTED_PROXY"))
+                .collect(),
+            chaos_mode: env_or("CHAOS_MODE", false),
+            chaos_fail_percent: env_or("CHAOS_FAIL_PERCENT", 10).min(100),
+            chaos_delay_percent: env_or("CHAOS_DELAY_PERCENT", 10).min(100),
+            chaos_delay_ms: env_or("CHAOS_DELAY_MS", 1_000),
+        }
+    }
+}
+
+fn env_list(key: &str, default: &[&str]) -> Vec<String> {
+    match env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => default.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,248 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    future::Future,
+    io,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use sqlx::{
+    sqlite::SqlitePoolOptions, Executor, Sqlite, SqliteConnection, SqlitePool, Transaction,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::Durability,
+    error::{self, AppError},
+};
+
+pub fn is_in_memory(url: &str) -> bool {
+    url.contains(":memory:") || url.contains("mode=memory")
+}
+
+pub async fn connect(url: &str, durability: Durability) -> Result<SqlitePool, sqlx::Error> {
+    info!("Using the {} database durability profile", durability);
+
+    let mut options = SqlitePoolOptions::new();
+    if is_in_memory(url) {
+        // An in-memory database lives only as long as a connection to it,
+        // so keep one open for the life of the pool.
+        info!("Using an in-memory database; data will not survive a restart");
+        options = options
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+
+    options
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                conn.execute(durability.pragmas()).await?;
+                Ok(())
+            })
+        })
+        .connect(url)
+        .await
+}
+
+// sqlx's SQLite migrator takes no lock of its own, so two instances
+// starting on one file could both try to apply the same migration. An
+// exclusive lock on a sidecar file makes the second wait for the first;
+// the lock is released when the returned file is dropped.
+pub async fn lock_migrations(url: &str) -> io::Result<Option<File>> {
+    if is_in_memory(url) {
+        return Ok(None);
+    }
+
+    let path = format!("{}.migrate.lock", file_path(url));
+    tokio::task::spawn_blocking(move || {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                info!("Waiting for another instance to finish migrating");
+                file.lock()?;
+            }
+            Err(TryLockError::Error(error)) => return Err(error),
+        }
+
+        Ok(Some(file))
+    })
+    .await
+    .expect("Migration lock task panicked")
+}
+
+fn file_path(url: &str) -> &str {
+    let path = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))
+        .unwrap_or(url);
+    path.split('?').next().unwrap_or(path)
+}
+
+// Rewriting user_version with its current value is a harmless write that
+// fails the same way a real insert would on a read-only file.
+pub async fn is_writable(db: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let (user_version,): (i64,) = sqlx::query_as("PRAGMA user_version").fetch_one(db).await?;
+
+    match sqlx::query(&format!("PRAGMA user_version = {}", user_version))
+        .execute(db)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(error) if error::is_read_only(&error) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+const CORE_TABLES: [&str; 1] = ["songs"];
+
+// Logs the row count of every application table so a wrong or empty
+// database is obvious in the startup output.
+pub async fn self_check(db: &SqlitePool) -> Result<(), String> {
+    let tables: Vec<(String,)> = sqlx::query_as(
+        "SELECT name FROM pragma_table_list WHERE schema = 'main' AND type = 'table'
+        AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' AND name != '_sqlx_migrations'
+        ORDER BY name",
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|error| error.to_string())?;
+
+    for core in CORE_TABLES {
+        if !tables.iter().any(|(name,)| name == core) {
+            return Err(format!("The {} table is missing", core));
+        }
+    }
+
+    for (name,) in tables {
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", name))
+            .fetch_one(db)
+            .await
+            .map_err(|error| error.to_string())?;
+
+        info!(table = name, rows = count, "Table row count");
+    }
+
+    Ok(())
+}
+
+pub type TxFuture<'c, T> = Pin<Box<dyn Future<Output = Result<T, AppError>> + Send + 'c>>;
+
+pub async fn with_tx<T, F>(db: &SqlitePool, f: F) -> Result<T, AppError>
+where
+    F: for<'c> FnOnce(&'c mut SqliteConnection) -> TxFuture<'c, T>,
+{
+    run(db.begin().await?, f).await
+}
+
+// A deferred transaction that reads before it writes cannot be upgraded
+// once another connection has committed in between, and SQLite fails it
+// with SQLITE_BUSY instead of waiting. Writers take the write lock up front
+// so they queue on the busy timeout and read what they are about to change.
+pub async fn with_write_tx<T, F>(db: &SqlitePool, f: F) -> Result<T, AppError>
+where
+    F: for<'c> FnOnce(&'c mut SqliteConnection) -> TxFuture<'c, T>,
+{
+    run(db.begin_with("BEGIN IMMEDIATE").await?, f).await
+}
+
+async fn run<T, F>(mut tx: Transaction<'static, Sqlite>, f: F) -> Result<T, AppError>
+where
+    F: for<'c> FnOnce(&'c mut SqliteConnection) -> TxFuture<'c, T>,
+{
+    match f(&mut tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(error) => {
+            tx.rollback().await?;
+            Err(error)
+        }
+    }
+}
+
+pub async fn timed<T>(slow_query_ms: u64, name: &str, query: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = query.await;
+    let elapsed = started.elapsed();
+
+    if slow_query_ms > 0 && elapsed >= Duration::from_millis(slow_query_ms) {
+        warn!(
+            query = name,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "Slow query"
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Another connection commits between the transaction's read and its
+    // write. A deferred transaction would fail that write with
+    // SQLITE_BUSY_SNAPSHOT; a write transaction makes the other writer wait.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn write_tx_holds_off_other_writers_between_read_and_write() {
+        let path = std::env::temp_dir().join(format!("server-db-test-{}.db", std::process::id()));
+        let db = connect(
+            &format!("sqlite://{}?mode=rwc", path.display()),
+            Durability::Safe,
+        )
+        .await
+        .unwrap();
+        sqlx::query("CREATE TABLE counter (value INTEGER NOT NULL)")
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO counter (value) VALUES (0)")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let other = db.clone();
+        let result = with_write_tx(&db, |tx| {
+            Box::pin(async move {
+                let (value,): (i64,) = sqlx::query_as("SELECT value FROM counter")
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                let writer = tokio::spawn(async move {
+                    sqlx::query("UPDATE counter SET value = value + 100")
+                        .execute(&other)
+                        .await
+                });
+                tokio::time::sleep(Duration::from_millis(200)).await;
+
+                sqlx::query("UPDATE counter SET value = ?")
+                    .bind(value + 1)
+                    .execute(&mut *tx)
+                    .await?;
+                Ok(writer)
+            })
+        })
+        .await;
+
+        let writer = result.expect("The read-then-write transaction failed");
+        writer.await.unwrap().unwrap();
+        let (value,): (i64,) = sqlx::query_as("SELECT value FROM counter")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(value, 101);
+
+        db.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+#[derive(Debug)]
+pub struct AppError {
+    status: StatusCode,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+impl AppError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.status)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        if is_read_only(&error) {
+            return Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The database is read-only, so changes cannot be saved right now",
+            );
+        }
+        if is_busy(&error) {
+            return Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The database is busy with other writes; retry the request",
+            );
+        }
+
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", error),
+        )
+    }
+}
+
+// SQLITE_READONLY and SQLITE_PERM, including their extended variants.
+pub fn is_read_only(error: &sqlx::Error) -> bool {
+    primary_code(error).is_some_and(|code| matches!(code, 3 | 8))
+}
+
+// SQLITE_BUSY and SQLITE_LOCKED, including BUSY_SNAPSHOT, once the busy
+// timeout has run out.
+fn is_busy(error: &sqlx::Error) -> bool {
+    primary_code(error).is_some_and(|code| matches!(code, 5 | 6))
+}
+
+fn primary_code(error: &sqlx::Error) -> Option<i32> {
+    let sqlx::Error::Database(error) = error else {
+        return None;
+    };
+
+    error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .map(|code| code & 0xff)
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(ErrorBody {
+                error: &self.message,
+            }),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{song_id::SongId, AppState};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayEvent {
+    pub song_id: SongId,
+    pub play_count: i64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NowPlayingEvent {
+    pub song_id: Option<SongId>,
+}
+
+#[derive(Clone, Debug)]
+enum StreamEvent {
+    Play(PlayEvent),
+    NowPlaying(NowPlayingEvent),
+}
+
+impl StreamEvent {
+    fn to_sse(&self) -> Event {
+        let event = match self {
+            Self::Play(play) => Event::default().event("play").json_data(play),
+            Self::NowPlaying(now_playing) => {
+                Event::default().event("now_playing").json_data(now_playing)
+            }
+        };
+        event.expect("Failed to serialize event")
+    }
+}
+
+#[derive(Clone)]
+pub struct Events {
+    plays: mpsc::UnboundedSender<PlayEvent>,
+    broadcast: broadcast::Sender<StreamEvent>,
+}
+
+impl Events {
+    pub fn new(window: Duration) -> Self {
+        let (plays, rx) = mpsc::unbounded_channel();
+        let (broadcast, _) = broadcast::channel(256);
+
+        tokio::spawn(coalesce_plays(rx, broadcast.clone(), window));
+
+        Self { plays, broadcast }
+    }
+
+    pub fn publish_play(&self, event: PlayEvent) {
+        self.plays.send(event).ok();
+    }
+
+    // Now-playing changes are rare and only the latest matters, so they
+    // skip the coalescing applied to plays.
+    pub fn publish_now_playing(&self, event: NowPlayingEvent) {
+        self.broadcast.send(StreamEvent::NowPlaying(event)).ok();
+    }
+}
+
+// Plays arriving within one window are merged into a single event per song
+// carrying the latest count, so a burst of plays produces one message. The
+// latest rather than the highest, since unplays and swaps lower the count.
+async fn coalesce_plays(
+    mut rx: mpsc::UnboundedReceiver<PlayEvent>,
+    broadcast: broadcast::Sender<StreamEvent>,
+    window: Duration,
+) {
+    while let Some(event) = rx.recv().await {
+        let mut pending = HashMap::new();
+        merge(&mut pending, event);
+
+        let deadline = tokio::time::sleep(window);
+        tokio::pin!(deadline);
+
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                event = rx.recv() => match event {
+                    Some(event) => merge(&mut pending, event),
+                    None => break,
+                },
+            }
+        }
+
+        for event in pending.into_values() {
+            broadcast.send(StreamEvent::Play(event)).ok();
+        }
+    }
+}
+
+fn merge(pending: &mut HashMap<SongId, PlayEvent>, event: PlayEvent) {
+    pending.insert(event.song_id, event);
+}
+
+pub async fn play_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.events.broadcast.subscribe();
+    let mut shutdown = state.shutdown.clone();
+
+    let events = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((Ok(event.to_sse()), rx)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .take_until(async move {
+        shutdown.wait_for(|stopping| *stopping).await.ok();
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_the_latest_count() {
+        let mut pending = HashMap::new();
+        for play_count in [3, 4, 3] {
+            merge(
+                &mut pending,
+                PlayEvent {
+                    song_id: SongId(1),
+                    play_count,
+                },
+            );
+        }
+
+        assert_eq!(pending[&SongId(1)].play_count, 3);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt::Write;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde::Deserialize;
+use sqlx::{prelude::FromRow, SqlitePool};
+use tracing::warn;
+
+use crate::{error::AppError, timestamp, AppState, Song};
+
+const CHUNK_ROWS: i64 = 500;
+
+const CSV_COLUMNS: &str = "id,title,artist,genre,play_count,duration_seconds,updated_at,created_at";
+
+const PLAY_CSV_COLUMNS: &str = "song_id,title,artist,played_at";
+
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    format: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct PlayExportParams {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(FromRow)]
+struct PlayRow {
+    id: i64,
+    song_id: i64,
+    title: String,
+    artist: String,
+    played_at: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+struct Cursor {
+    db: SqlitePool,
+    format: Format,
+    after_id: i64,
+    remaining: Option<i64>,
+    started: bool,
+    done: bool,
+}
+
+struct PlayCursor {
+    db: SqlitePool,
+    from: String,
+    to: String,
+    after_id: i64,
+    started: bool,
+    done: bool,
+}
+
+// Rows are read in id order, CHUNK_ROWS at a time, and each chunk is
+// fetched only once the client has taken the previous one. A slow reader
+// therefore holds back the export instead of making the server buffer
+// the table, and no connection or read transaction is held in between.
+pub async fn export_songs(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, AppError> {
+    let format = match params.format.as_deref().unwrap_or("json") {
+        "json" => Format::Json,
+        "csv" => Format::Csv,
+        _ => return Err(AppError::bad_request("format must be json or csv")),
+    };
+    if params.limit.is_some_and(|limit| limit < 1) {
+        return Err(AppError::bad_request("limit must be at least 1"));
+    }
+
+    let cursor = Cursor {
+        db: state.db.clone(),
+        format,
+        after_id: 0,
+        remaining: params.limit,
+        started: false,
+        done: false,
+    };
+    let body = Body::from_stream(stream::try_unfold(cursor, next_chunk));
+
+    Ok(match format {
+        Format::Json => attachment("application/json", "songs.json", body),
+        Format::Csv => attachment(CSV_CONTENT_TYPE, "songs.csv", body),
+    })
+}
+
+// Both ends of the window are required so a forgotten filter cannot turn
+// into a dump of the entire play history. Rows stream in the same
+// chunked, pull-driven way as the song export.
+pub async fn export_plays(
+    State(state): State<AppState>,
+    Query(params): Query<PlayExportParams>,
+) -> Result<Response, AppError> {
+    let (Some(from), Some(to)) = (params.from.as_deref(), params.to.as_deref()) else {
+        return Err(AppError::bad_request("from and to are both required"));
+    };
+    let (from, to) = timestamp::parse_window(Some(from), Some(to))?;
+
+    let cursor = PlayCursor {
+        db: state.db.clone(),
+        from: timestamp::to_db(from.expect("from was given")),
+        to: timestamp::to_db(to.expect("to was given")),
+        after_id: 0,
+        started: false,
+        done: false,
+    };
+    let body = Body::from_stream(stream::try_unfold(cursor, next_play_chunk));
+
+    Ok(attachment(CSV_CONTENT_TYPE, "plays.csv", body))
+}
+
+fn attachment(content_type: &str, filename: &str, body: Body) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn next_chunk(mut cursor: Cursor) -> Result<Option<(Bytes, Cursor)>, sqlx::Error> {
+    if cursor.done {
+        return Ok(None);
+    }
+
+    let limit = cursor
+        .remaining
+        .map_or(CHUNK_ROWS, |remaining| remaining.min(CHUNK_ROWS));
+    let songs = sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id > ? ORDER BY id LIMIT ?")
+        .bind(cursor.after_id)
+        .bind(limit)
+        .fetch_all(&cursor.db)
+        .await
+        .inspect_err(|error| warn!(%error, "Export stopped by a database error"))?;
+
+    let mut chunk = String::new();
+    if !cursor.started {
+        match cursor.format {
+            Format::Json => chunk.push('['),
+            Format::Csv => {
+                chunk.push_str(CSV_COLUMNS);
+                chunk.push_str("\r\n");
+            }
+        }
+        cursor.started = true;
+    }
+
+    for song in &songs {
+        match cursor.format {
+            Format::Json => {
+                // Ids start at 1, so after_id is 0 only before the first row.
+                if cursor.after_id > 0 {
+                    chunk.push(',');
+                }
+                chunk.push_str(&serde_json::to_string(song).expect("Failed to serialize song"));
+            }
+            Format::Csv => csv_row(&mut chunk, song),
+        }
+        cursor.after_id = song.id;
+    }
+
+    if let Some(remaining) = &mut cursor.remaining {
+        *remaining -= songs.len() as i64;
+    }
+    if (songs.len() as i64) < limit || cursor.remaining == Some(0) {
+        cursor.done = true;
+        if cursor.format == Format::Json {
+            chunk.push(']');
+        }
+    }
+
+    Ok(Some((Bytes::from(chunk), cursor)))
+}
+
+async fn next_play_chunk(
+    mut cursor: PlayCursor,
+) -> Result<Option<(Bytes, PlayCursor)>, sqlx::Error> {
+    if cursor.done {
+        return Ok(None);
+    }
+
+    let plays = sqlx::query_as::<_, PlayRow>(
+        "SELECT plays.id, plays.song_id, songs.title, songs.artist, plays.played_at
+        FROM plays JOIN songs ON songs.id = plays.song_id
+        WHERE plays.id > ? AND plays.played_at >= ? AND plays.played_at < ?
+        ORDER BY plays.id LIMIT ?",
+    )
+    .bind(cursor.after_id)
+    .bind(&cursor.from)
+    .bind(&cursor.to)
+    .bind(CHUNK_ROWS)
+    .fetch_all(&cursor.db)
+    .await
+    .inspect_err(|error| warn!(%error, "Play export stopped by a database error"))?;
+
+    let mut chunk = String::new();
+    if !cursor.started {
+        chunk.push_str(PLAY_CSV_COLUMNS);
+        chunk.push_str("\r\n");
+        cursor.started = true;
+    }
+
+    for play in &plays {
+        write!(
+            chunk,
+            "{},{},{},{}\r\n",
+            play.song_id,
+            csv_field(&play.title),
+            csv_field(&play.artist),
+            play.played_at
+        )
+        .expect("Writing to a String cannot fail");
+        cursor.after_id = play.id;
+    }
+
+    cursor.done = (plays.len() as i64) < CHUNK_ROWS;
+
+    Ok(Some((Bytes::from(chunk), cursor)))
+}
+
+fn csv_row(out: &mut String, song: &Song) {
+    let duration = song
+        .duration_seconds
+        .map_or(String::new(), |duration| duration.to_string());
+
+    write!(
+        out,
+        "{},{},{},{},{},{},{},{}\r\n",
+        song.id,
+        csv_field(&song.title),
+        csv_field(&song.artist),
+        csv_field(&song.genre),
+        song.play_count,
+        duration,
+        song.updated_at,
+        song.created_at
+    )
+    .expect("Writing to a String cannot fail");
+}
+
+// RFC 4180: quote fields containing a delimiter, quote or line break, and
+// double any quotes inside them.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+
+pub const SONG_FIELDS: [&str; 8] = [
+    "id",
+    "title",
+    "artist",
+    "genre",
+    "play_count",
+    "duration_seconds",
+    "updated_at",
+    "created_at",
+];
+
+#[derive(Deserialize)]
+pub struct FieldsQuery {
+    pub fields: Option<String>,
+}
+
+pub struct Fields(Option<Vec<String>>);
+
+impl Fields {
+    pub fn parse(raw: Option<&str>) -> Result<Self, AppError> {
+        let Some(raw) = raw else {
+            return Ok(Self(None));
+        };
+
+        let mut fields = Vec::new();
+        for field in raw.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if !SONG_FIELDS.contains(&field) {
+                return Err(AppError::bad_request(format!("Unknown field: {}", field)));
+            }
+            if !fields.iter().any(|f| f == field) {
+                fields.push(field.to_string());
+            }
+        }
+
+        if fields.is_empty() {
+            return Err(AppError::bad_request("fields must name at least one field"));
+        }
+
+        Ok(Self(Some(fields)))
+    }
+
+    pub fn names(&self) -> Option<&[String]> {
+        self.0.as_deref()
+    }
+
+    pub fn project<T: Serialize>(&self, value: &T) -> Value {
+        let value = serde_json::to_value(value).expect("Failed to serialize value");
+
+        let Some(fields) = &self.0 else {
+            return value;
+        };
+        let Value::Object(mut object) = value else {
+            return value;
+        };
+
+        let mut projected = Map::new();
+        for field in fields {
+            if let Some(value) = object.remove(field) {
+                projected.insert(field.clone(), value);
+            }
+        }
+        Value::Object(projected)
+    }
+
+    pub fn project_all<T: Serialize>(&self, values: &[T]) -> Value {
+        Value::Array(values.iter().map(|value| self.project(value)).collect())
+    }
+}
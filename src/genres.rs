@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{
+    db,
+    error::AppError,
+    pagination::{self, Pagination},
+    text,
+    version::ApiVersion,
+    AppState, Song,
+};
+
+const DEFAULT_TRENDING_DAYS: i64 = 7;
+const MAX_TRENDING_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct TrendingParams {
+    days: Option<i64>,
+}
+
+// Songs tied on plays share a rank, as with ?with_rank on a play.
+#[derive(Serialize, FromRow)]
+pub struct TrendingSong {
+    rank: i64,
+    plays_in_window: i64,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    song: Song,
+}
+
+// Counts come from the plays history over the last `days` days, not from
+// lifetime play_count. A genre with no recent plays, or no songs at all,
+// gives an empty list.
+pub async fn trending_songs(
+    State(state): State<AppState>,
+    Path(genre): Path<String>,
+    Query(params): Query<TrendingParams>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let days = params.days.unwrap_or(DEFAULT_TRENDING_DAYS);
+    if !(1..=MAX_TRENDING_DAYS).contains(&days) {
+        return Err(AppError::bad_request(format!(
+            "days must be between 1 and {}",
+            MAX_TRENDING_DAYS
+        )));
+    }
+    let genre = text::normalize(genre.trim());
+    let window = format!("-{} days", days);
+    let limit = page.limit(state.config.max_page_size);
+    let offset = page.offset();
+
+    let (total, songs) = db::timed(
+        state.config.slow_query_ms,
+        "genre_trending",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let (total,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(DISTINCT plays.song_id)
+                    FROM plays JOIN songs ON songs.id = plays.song_id
+                    WHERE songs.genre = ? COLLATE NOCASE
+                    AND plays.played_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?)",
+                )
+                .bind(&genre)
+                .bind(&window)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                let songs = sqlx::query_as::<_, TrendingSong>(
+                    "SELECT RANK() OVER (ORDER BY COUNT(*) DESC) AS rank,
+                    COUNT(*) AS plays_in_window, songs.*
+                    FROM plays JOIN songs ON songs.id = plays.song_id
+                    WHERE songs.genre = ? COLLATE NOCASE
+                    AND plays.played_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?)
+                    GROUP BY songs.id ORDER BY plays_in_window DESC, songs.id LIMIT ? OFFSET ?",
+                )
+                .bind(&genre)
+                .bind(&window)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                Ok((total, songs))
+            })
+        }),
+    )
+    .await?;
+
+    Ok(pagination::paged(version, songs, total, limit, offset))
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+
+use crate::{search::SearchParams, text, Song};
+
+#[derive(Serialize)]
+pub struct Match {
+    field: &'static str,
+    start: usize,
+    len: usize,
+}
+
+// Offsets are in characters and compare ASCII case-insensitively, which is
+// how SQLite's LIKE and NOCASE matched the row in the first place.
+pub fn matches(params: &SearchParams, song: &Song) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    for (field, term) in params.filters() {
+        let value = match field {
+            "title" => &song.title,
+            "artist" => &song.artist,
+            _ => &song.genre,
+        };
+
+        let haystack = fold(value);
+        let needle = fold(&text::normalize(term));
+        if needle.is_empty() {
+            continue;
+        }
+
+        let ranges = if params.exact {
+            whole(haystack == needle, needle.len())
+        } else if params.fuzzy {
+            subsequence(&haystack, &needle)
+        } else if params.prefix {
+            whole(haystack.starts_with(&needle), needle.len())
+        } else if params.all_tokens() {
+            needle
+                .split(|c| c.is_whitespace())
+                .filter(|token| !token.is_empty())
+                .flat_map(|token| occurrences(&haystack, token))
+                .collect()
+        } else {
+            occurrences(&haystack, &needle)
+        };
+
+        matches.extend(
+            ranges
+                .into_iter()
+                .map(|(start, len)| Match { field, start, len }),
+        );
+    }
+
+    matches
+}
+
+fn fold(value: &str) -> Vec<char> {
+    value.chars().map(|c| c.to_ascii_lowercase()).collect()
+}
+
+fn whole(matched: bool, len: usize) -> Vec<(usize, usize)> {
+    if matched {
+        vec![(0, len)]
+    } else {
+        Vec::new()
+    }
+}
+
+fn occurrences(haystack: &[char], needle: &[char]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start + needle.len() <= haystack.len() {
+        if haystack[start..start + needle.len()] == *needle {
+            ranges.push((start, needle.len()));
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+
+    ranges
+}
+
+// Matches each character of the needle in order and merges adjacent
+// positions into runs, so "hey" in "hey jude" is one range, not three.
+fn subsequence(haystack: &[char], needle: &[char]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
+
+    for c in needle {
+        let Some(offset) = haystack[position..].iter().position(|h| h == c) else {
+            return Vec::new();
+        };
+        let index = position + offset;
+
+        match ranges.last_mut() {
+            Some((start, len)) if *start + *len == index => *len += 1,
+            _ => ranges.push((index, 1)),
+        }
+        position = index + 1;
+    }
+
+    ranges
+}
@@ -0,0 +1,69 @@
+use axum::http::HeaderMap;
+use sqlx::SqliteConnection;
+
+use crate::{error::AppError, Song};
+
+pub const HEADER: &str = "idempotency-key";
+
+const MAX_KEY_LENGTH: usize = 250;
+
+pub fn key(headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+
+    let key = value
+        .to_str()
+        .map_err(|_| AppError::bad_request("Idempotency-Key must be visible ASCII"))?
+        .trim();
+
+    if key.is_empty() {
+        return Err(AppError::bad_request("Idempotency-Key must not be empty"));
+    }
+    if key.len() > MAX_KEY_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Idempotency-Key must be at most {} characters",
+            MAX_KEY_LENGTH
+        )));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+// Expired keys are purged on the way in, so a key reused after the TTL
+// is treated as new. A TTL of 0 keeps keys forever.
+pub async fn replay(
+    conn: &mut SqliteConnection,
+    key: &str,
+    ttl_secs: u64,
+) -> Result<Option<Song>, AppError> {
+    if ttl_secs > 0 {
+        sqlx::query(
+            "DELETE FROM idempotency_keys
+            WHERE created_at <= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?)",
+        )
+        .bind(format!("-{} seconds", ttl_secs))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let song = sqlx::query_as::<_, Song>(
+        "SELECT songs.* FROM idempotency_keys JOIN songs ON songs.id = idempotency_keys.song_id
+        WHERE idempotency_keys.key = ?",
+    )
+    .bind(key)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(song)
+}
+
+pub async fn record(conn: &mut SqliteConnection, key: &str, song_id: i64) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO idempotency_keys (key, song_id) VALUES (?, ?)")
+        .bind(key)
+        .bind(song_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+
+use crate::{
+    error::AppError,
+    pagination::Pagination,
+    search::{self, SearchQuery},
+    AppState, Song,
+};
+
+const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+// Same filters and limit/offset pagination as /songs/search, wrapped in a
+// JSON:API document with meta.total and pagination links.
+pub async fn songs(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    SearchQuery(params): SearchQuery,
+    Query(page): Query<Pagination>,
+    Query(raw): Query<Vec<(String, String)>>,
+) -> Result<impl IntoResponse, AppError> {
+    params.validate(state.config.max_search_filters)?;
+
+    let limit = page.limit(state.config.max_page_size);
+    let offset = page.offset();
+    let (total, songs) = search::fetch_songs(&state, &params, limit, offset).await?;
+
+    let link = |offset: i64| {
+        let mut query = raw
+            .iter()
+            .filter(|(key, _)| key != "limit" && key != "offset")
+            .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+            .collect::<Vec<_>>();
+        query.push(format!("limit={}", limit));
+        query.push(format!("offset={}", offset));
+        Value::String(format!("{}?{}", uri.path(), query.join("&")))
+    };
+
+    let last = if total == 0 {
+        0
+    } else {
+        (total - 1) / limit * limit
+    };
+    let previous = (offset > 0).then(|| link((offset - limit).max(0)));
+    let next = (offset.saturating_add(limit) < total).then(|| link(offset + limit));
+
+    let document = json!({
+        "data": songs.iter().map(resource).collect::<Vec<_>>(),
+        "meta": { "total": total },
+        "links": {
+            "self": link(offset),
+            "first": link(0),
+            "prev": previous,
+            "next": next,
+            "last": link(last),
+        },
+    });
+
+    Ok(([(header::CONTENT_TYPE, MEDIA_TYPE)], Json(document)))
+}
+
+fn resource(song: &Song) -> Value {
+    json!({
+        "type": "songs",
+        "id": song.id.to_string(),
+        "attributes": {
+            "title": song.title,
+            "artist": song.artist,
+            "genre": song.genre,
+            "play_count": song.play_count,
+            "duration_seconds": song.duration_seconds,
+            "updated_at": song.updated_at,
+            "created_at": song.created_at,
+        },
+    })
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
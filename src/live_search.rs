@@ -0,0 +1,99 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use serde_json::json;
+use tokio::time::{sleep_until, Instant};
+use tracing::debug;
+
+use crate::{
+    error::AppError,
+    search::{self, SearchParams},
+    AppState, Song,
+};
+
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+type SearchFuture = Pin<Box<dyn Future<Output = Result<Vec<Song>, AppError>> + Send>>;
+
+pub async fn search_socket(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| run(socket, state))
+}
+
+// Each text message is a JSON SearchParams. A search starts once the
+// client has been quiet for DEBOUNCE, and any newer message drops the
+// search in flight, so only the latest terms are ever answered. Replies
+// are sent one at a time, so a slow reader holds back further searches
+// rather than piling up results.
+async fn run(mut socket: WebSocket, state: AppState) {
+    let mut shutdown = state.shutdown.clone();
+    let mut pending: Option<SearchParams> = None;
+    let mut deadline = Instant::now();
+    let mut search: Option<SearchFuture> = None;
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    search = None;
+                    match parse(&text, state.config.max_search_filters) {
+                        Ok(params) => {
+                            pending = Some(params);
+                            deadline = Instant::now() + DEBOUNCE;
+                        }
+                        Err(error) => {
+                            pending = None;
+                            if send_error(&mut socket, &error).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = sleep_until(deadline), if pending.is_some() => {
+                let params = pending.take().expect("pending search");
+                let state = state.clone();
+                search = Some(Box::pin(async move {
+                    let limit = state.config.max_page_size;
+                    let (_, songs) = search::fetch_songs(&state, &params, limit, 0).await?;
+                    Ok(songs)
+                }));
+            },
+            result = async { search.as_mut().expect("search in flight").await }, if search.is_some() => {
+                search = None;
+                let sent = match result {
+                    Ok(songs) => {
+                        let body = serde_json::to_string(&songs).expect("Failed to serialize songs");
+                        socket.send(Message::Text(body)).await
+                    }
+                    Err(error) => send_error(&mut socket, &error).await,
+                };
+                if sent.is_err() {
+                    break;
+                }
+            },
+            _ = async { shutdown.wait_for(|stopping| *stopping).await.is_ok() } => break,
+        }
+    }
+
+    debug!("Live search socket closed");
+}
+
+fn parse(text: &str, max_filters: usize) -> Result<SearchParams, AppError> {
+    let params: SearchParams = serde_json::from_str(text)
+        .map_err(|error| AppError::bad_request(format!("Invalid search: {}", error)))?;
+    params.validate(max_filters)?;
+    Ok(params)
+}
+
+async fn send_error(socket: &mut WebSocket, error: &AppError) -> Result<(), axum::Error> {
+    let body = json!({ "error": error.message() }).to_string();
+    socket.send(Message::Text(body)).await
+}
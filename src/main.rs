@@ -1,17 +1,58 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{from_fn, from_fn_with_state},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
     Json, Router,
 };
+use config::{Config, LogFormat};
+use error::AppError;
+use events::{Events, PlayEvent};
+use fields::{Fields, FieldsQuery};
+use routes::Routes;
+use search_stats::SearchStats;
 use serde::{Deserialize, Serialize};
-use sqlx::{migrate::MigrateDatabase, prelude::FromRow, Sqlite, SqlitePool};
-use tokio::sync::Mutex;
+use song_id::SongId;
+use sqlx::{migrate::MigrateDatabase, prelude::FromRow, QueryBuilder, Sqlite, SqlitePool};
+use tokio::sync::{watch, Mutex};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
-const DB_URL: &str = "sqlite://songs.db";
+mod admin;
+mod artists;
+mod bulk;
+mod client_ip;
+mod conditional;
+mod config;
+mod db;
+mod error;
+mod events;
+mod export;
+mod fields;
+mod genres;
+mod highlight;
+mod idempotency;
+mod jsonapi;
+mod live_search;
+mod meta;
+mod middleware;
+mod now_playing;
+mod pagination;
+mod quality;
+mod recent;
+mod routes;
+mod search;
+mod search_stats;
+mod song_id;
+mod stats;
+mod summaries;
+mod tags;
+mod text;
+mod timestamp;
+mod version;
 
 #[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
 struct Song {
@@ -24,155 +65,1128 @@ struct Song {
 
     #[serde(default)]
     play_count: i64,
+    #[serde(default)]
+    duration_seconds: Option<i64>,
+
+    #[serde(default)]
+    updated_at: String,
+    #[serde(default)]
+    created_at: String,
+
+    // Only selected, and only serialized, under sort=trending.
+    #[sqlx(default)]
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    trending_score: Option<f64>,
+}
+
+// Matches the VARCHAR(250) columns. Lengths are counted in characters
+// after NFC normalization, i.e. as the value will be stored.
+const MAX_FIELD_LENGTH: usize = 250;
+
+impl Song {
+    fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.duration_seconds.is_some_and(|duration| duration <= 0) {
+            errors.push("duration_seconds must be a positive integer".to_string());
+        }
+
+        for (field, value) in [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("genre", &self.genre),
+        ] {
+            if value.trim().is_empty() {
+                errors.push(format!("{} must not be empty", field));
+                continue;
+            }
+
+            let length = text::normalize(value).chars().count();
+            if length > MAX_FIELD_LENGTH {
+                errors.push(format!(
+                    "{} is {} characters long; the limit is {}",
+                    field, length, MAX_FIELD_LENGTH
+                ));
+            }
+        }
+
+        errors
+    }
+
+    fn validation_error(&self) -> Option<String> {
+        self.validation_errors().into_iter().next()
+    }
+}
+
+#[derive(Serialize)]
+struct SongDetail {
+    #[serde(flatten)]
+    song: Song,
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PlayParams {
+    #[serde(default)]
+    with_rank: bool,
+}
+
+// Songs tied on play_count share a rank, so a song is ranked one place
+// behind however many songs have strictly more plays.
+#[derive(Serialize)]
+struct RankedPlay {
+    song: Song,
+    rank: i64,
+}
+
+#[derive(Deserialize)]
+struct LookupParams {
+    title: String,
+    artist: String,
+}
+
+#[derive(Deserialize)]
+struct IdsParams {
+    ids: Option<String>,
 }
 
 #[derive(Serialize)]
-struct SongNotFound<'a> {
+struct SongsById {
+    items: Vec<Song>,
+    missing: Vec<SongId>,
+}
+
+#[derive(Serialize)]
+struct LookupConflict<'a> {
     error: &'a str,
+    matches: Vec<Song>,
 }
 
 #[derive(Clone)]
 struct AppState {
     db: SqlitePool,
+    config: Arc<Config>,
+    events: Events,
+    shutdown: watch::Receiver<bool>,
+    search_stats: Arc<SearchStats>,
+}
+
+impl AppState {
+    fn new(db: SqlitePool, config: Config, shutdown: watch::Receiver<bool>) -> Self {
+        Self {
+            db,
+            events: Events::new(Duration::from_millis(config.play_event_window_ms)),
+            config: Arc::new(config),
+            shutdown,
+            search_stats: Arc::new(SearchStats::new()),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let user_count = Arc::new(Mutex::new(0));
+    let config = Config::from_env();
+
+    let subscriber = tracing_subscriber::fmt().with_env_filter(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    );
+    match config.log_format {
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    let db = open_database(&config).await;
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let summary_task = (config.summary_interval_secs > 0).then(|| {
+        tokio::spawn(summaries::run_periodic(
+            db.clone(),
+            Duration::from_secs(config.summary_interval_secs),
+            shutdown_rx.clone(),
+        ))
+    });
+
+    let app = app(AppState::new(db, config, shutdown_rx));
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
+        .await
+        .expect("Unable to bind to port 8080");
+    info!("The server is currently listening on localhost:8080.");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        shutdown_tx.send(true).ok();
+    })
+    .await
+    .expect("Infallible server error");
+
+    if let Some(summary_task) = summary_task {
+        summary_task.await.ok();
+    }
+}
+
+async fn open_database(config: &Config) -> SqlitePool {
+    // Only SQLite is supported. The queries lean on SQLite-specific SQL
+    // (strftime, COLLATE NOCASE, INSERT OR IGNORE, RETURNING on upserts) and
+    // the pool is a SqlitePool throughout, so other backends are refused up
+    // front rather than failing on the first request.
+    if !config.database_url.starts_with("sqlite:") {
+        panic!(
+            "DATABASE_URL must be a sqlite: URL; other backends such as Postgres are not supported"
+        );
+    }
+
+    let migration_lock = db::lock_migrations(&config.database_url)
+        .await
+        .expect("Failed to take the migration lock");
 
-    if !Sqlite::database_exists(DB_URL).await.unwrap_or(false) {
-        match Sqlite::create_database(DB_URL).await {
+    if !db::is_in_memory(&config.database_url)
+        && !Sqlite::database_exists(&config.database_url)
+            .await
+            .unwrap_or(false)
+    {
+        match Sqlite::create_database(&config.database_url).await {
             Ok(_) => {}
             Err(error) => panic!("error: {}", error),
         }
     }
 
-    let db = SqlitePool::connect(DB_URL)
+    let db = db::connect(&config.database_url, config.db_durability)
         .await
         .expect("Failed to connect to database");
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS songs (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        title VARCHAR(250) NOT NULL,
-        artist VARCHAR(250) NOT NULL,
-        genre VARCHAR(250) NOT NULL,
-        play_count INTEGER DEFAULT 0);",
-    )
-    .execute(&db)
-    .await
-    .expect("Failed to create table");
+    sqlx::migrate!()
+        .run(&db)
+        .await
+        .expect("Failed to run migrations");
+    drop(migration_lock);
+
+    if let Err(error) = db::self_check(&db).await {
+        panic!("Startup self-check failed: {}", error);
+    }
 
-    let state = AppState { db };
+    if !db::is_writable(&db)
+        .await
+        .expect("Failed to check database writability")
+    {
+        warn!("The database is read-only; writes will fail with 503 until it is writable");
+    }
+
+    db
+}
 
-    let app = Router::new()
+fn app(state: AppState) -> Router {
+    let mut app = Routes::new()
+        .route("/", get(meta::index))
+        .route("/version", get(meta::version))
+        .route("/songs", get(get_songs))
+        .route("/songs/new", post(add_song))
+        .route("/songs/bulk", post(add_songs))
+        .route("/songs/recategorize", post(bulk::recategorize))
+        .route("/songs/tag", post(bulk::tag_matching))
+        .route("/songs/validate", post(bulk::validate_songs))
+        .route(
+            "/songs/search",
+            get(search::search_song).post(search::search_song_post),
+        )
         .route(
-            "/",
-            get(|| async { "Welcome to the Rust-powered web server!" }),
+            "/songs/search/explain",
+            get(search::explain_search)
+                .route_layer(from_fn_with_state(state.clone(), middleware::require_admin)),
         )
+        .route("/songs/lookup", get(lookup_song))
+        .route("/songs/export", get(export::export_songs))
+        .route("/jsonapi/songs", get(jsonapi::songs))
+        .route("/songs/added", get(recent::added_songs))
+        .route("/songs/modified", get(recent::modified_songs))
+        .route("/songs/played", get(recent::played_songs))
+        .route("/songs/incomplete", get(quality::incomplete_songs))
+        .route("/songs/:id", get(get_song).put(update_song))
+        .route("/songs/play/:id", get(play_song))
+        .route("/songs/:id/unplay", post(unplay_song))
+        .route("/songs/:id/plays/timeseries", get(stats::play_timeseries))
+        .route("/songs/:id/tags", post(tags::add_tag))
+        .route("/songs/:id/tags/:tag", delete(tags::remove_tag))
+        .route("/artists/suggest", get(artists::suggest_artists))
+        .route("/artists/:name/songs", get(artists::artist_songs))
+        .route("/artists/:name/stats", get(artists::artist_stats))
         .route(
+            "/now-playing",
+            get(now_playing::get_now_playing).put(now_playing::set_now_playing),
+        )
+        .route("/plays/export.csv", get(export::export_plays))
+        .route("/genres/:genre/trending", get(genres::trending_songs))
+        .route("/stats", get(stats::library_stats))
+        .route("/stats/plays/histogram", get(stats::play_histogram))
+        .route("/stats/percentiles", get(stats::play_percentiles))
+        .nest("/admin", admin::routes(state.clone()));
+
+    if state.config.enable_visit_counter {
+        let user_count = Arc::new(Mutex::new(0));
+        app = app.route(
             "/count",
             get(|| async move {
                 let mut user_count = user_count.lock().await;
                 *user_count += 1;
                 format!("Visit count: {}", *user_count)
             }),
-        )
-        .route("/songs/new", post(add_song))
-        .route("/songs/search", get(search_song))
-        .route("/songs/play/:id", get(play_song))
-        .with_state(state);
+        );
+    }
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
-        .await
-        .expect("Unable to bind to port 8080");
-    println!("The server is currently listening on localhost:8080.");
-    axum::serve(listener, app)
+    // Chaos is wired in only for debug builds, so a stray CHAOS_MODE in a
+    // production environment cannot switch it on.
+    if state.config.chaos_mode {
+        if cfg!(debug_assertions) {
+            warn!(
+                fail_percent = state.config.chaos_fail_percent,
+                delay_percent = state.config.chaos_delay_percent,
+                delay_ms = state.config.chaos_delay_ms,
+                "CHAOS MODE IS ACTIVE: requests will be randomly delayed or failed"
+            );
+            app = app
+                .map(|router| router.layer(from_fn_with_state(state.clone(), middleware::chaos)));
+        } else {
+            warn!("CHAOS_MODE is set but ignored because this is a release build");
+        }
+    }
+
+    // Routes added after these layers are exempt from them: the streams
+    // stay open for as long as the client is connected, and /health must
+    // answer even when the server is saturated.
+    app.map(|router| {
+        router
+            .layer(from_fn_with_state(
+                state.clone(),
+                middleware::request_timeout,
+            ))
+            .layer(from_fn_with_state(
+                middleware::concurrency_limiter(&state.config),
+                middleware::limit_concurrency,
+            ))
+    })
+    .route("/health", get(meta::health))
+    .route("/events/plays", get(events::play_events))
+    .route("/ws", get(live_search::search_socket))
+    .into_router(state.config.suggest_routes)
+    .layer(from_fn(middleware::require_json))
+    .layer(from_fn_with_state(
+        state.clone(),
+        middleware::limit_query_string,
+    ))
+    .layer(middleware::cors(&state.config))
+    .with_state(state)
+}
+
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
         .await
-        .expect("Infallible server error");
+        .expect("Failed to listen for shutdown signal");
 }
 
+// A retried request carrying the same Idempotency-Key gets the song the
+// first attempt created instead of inserting a duplicate.
 #[axum::debug_handler]
-async fn add_song(State(state): State<AppState>, Json(payload): Json<Song>) -> Json<Song> {
-    let result = sqlx::query("INSERT INTO songs (title, artist, genre) VALUES (?, ?, ?)")
-        .bind(&payload.title)
-        .bind(&payload.artist)
-        .bind(&payload.genre)
-        .execute(&state.db)
-        .await
-        .expect("Failed to insert song");
+async fn add_song(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Song>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(error) = payload.validation_error() {
+        return Err(AppError::unprocessable(error));
+    }
+    let key = idempotency::key(&headers)?;
+    let max_songs = state.config.max_songs;
+    let ttl_secs = state.config.idempotency_key_ttl_secs;
 
-    Json(Song {
-        id: result.last_insert_rowid(),
-        ..payload
-    })
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "add_song",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                if let Some(key) = &key {
+                    if let Some(song) = idempotency::replay(&mut *tx, key, ttl_secs).await? {
+                        return Ok(song);
+                    }
+                }
+
+                let song = sqlx::query_as::<_, Song>(
+                    "INSERT INTO songs (title, artist, genre, duration_seconds) SELECT ?, ?, ?, ?
+                    WHERE ? = 0 OR (SELECT COUNT(*) FROM songs) < ? RETURNING *",
+                )
+                .bind(text::normalize(&payload.title))
+                .bind(text::normalize(&payload.artist))
+                .bind(text::normalize(&payload.genre))
+                .bind(payload.duration_seconds)
+                .bind(max_songs)
+                .bind(max_songs)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| quota_exceeded(max_songs))?;
+
+                if let Some(key) = &key {
+                    idempotency::record(&mut *tx, key, song.id).await?;
+                }
+
+                Ok(song)
+            })
+        }),
+    )
+    .await?;
+
+    let location = format!("/songs/{}", song.id);
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, location)],
+        Json(song),
+    ))
 }
 
-async fn search_song(
+// A batch that would exceed MAX_SONGS is rejected as a whole, so a bulk
+// insert either fully succeeds or leaves the table untouched.
+async fn add_songs(
     State(state): State<AppState>,
-    Query(params): Query<HashMap<String, String>>,
-) -> Json<Vec<Song>> {
-    let mut query_builder = vec![String::from("SELECT * FROM songs ")];
+    Json(payload): Json<Vec<Song>>,
+) -> Result<impl IntoResponse, AppError> {
+    for (index, song) in payload.iter().enumerate() {
+        if let Some(error) = song.validation_error() {
+            return Err(AppError::unprocessable(format!(
+                "Song {}: {}",
+                index, error
+            )));
+        }
+    }
+    let max_songs = state.config.max_songs;
+
+    let songs = db::timed(
+        state.config.slow_query_ms,
+        "add_songs",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                if max_songs > 0 {
+                    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM songs")
+                        .fetch_one(&mut *tx)
+                        .await?;
+
+                    if count + payload.len() as i64 > max_songs {
+                        return Err(quota_exceeded(max_songs));
+                    }
+                }
+
+                let mut songs = Vec::with_capacity(payload.len());
+                for song in payload {
+                    let song = sqlx::query_as::<_, Song>(
+                        "INSERT INTO songs (title, artist, genre, duration_seconds)
+                        VALUES (?, ?, ?, ?) RETURNING *",
+                    )
+                    .bind(text::normalize(&song.title))
+                    .bind(text::normalize(&song.artist))
+                    .bind(text::normalize(&song.genre))
+                    .bind(song.duration_seconds)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    songs.push(song);
+                }
+
+                Ok(songs)
+            })
+        }),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(songs)))
+}
+
+fn quota_exceeded(max_songs: i64) -> AppError {
+    AppError::conflict(format!("Song quota of {} would be exceeded", max_songs))
+}
 
-    for (key, value) in params {
-        if key != "title" && key != "artist" && key != "genre" {
-            continue;
+// Songs come back in the order their ids were asked for; ids with no song
+// are listed under missing. The id count is capped at MAX_PAGE_SIZE.
+async fn get_songs(
+    State(state): State<AppState>,
+    Query(params): Query<IdsParams>,
+) -> Result<Json<SongsById>, AppError> {
+    let raw = params.ids.as_deref().unwrap_or("");
+    let mut ids: Vec<SongId> = Vec::new();
+    for id in raw.split(',').filter(|id| !id.trim().is_empty()) {
+        let id = id.parse()?;
+        if !ids.contains(&id) {
+            ids.push(id);
         }
+    }
 
-        if query_builder.len() == 1 {
-            query_builder.push(String::from("WHERE "));
-        } else {
-            query_builder.push(String::from("AND "));
+    if ids.is_empty() {
+        return Err(AppError::bad_request("ids must list at least one song id"));
+    }
+    let max_ids = state.config.max_page_size as usize;
+    if ids.len() > max_ids {
+        return Err(AppError::bad_request(format!(
+            "ids may list at most {} song ids",
+            max_ids
+        )));
+    }
+
+    let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM songs WHERE id IN (");
+    let mut separated = builder.separated(", ");
+    for id in &ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    let mut found = db::timed(
+        state.config.slow_query_ms,
+        "get_songs",
+        builder.build_query_as::<Song>().fetch_all(&state.db),
+    )
+    .await?;
+
+    let mut items = Vec::with_capacity(found.len());
+    let mut missing = Vec::new();
+    for id in ids {
+        match found.iter().position(|song| song.id == id.0) {
+            Some(index) => items.push(found.swap_remove(index)),
+            None => missing.push(id),
         }
+    }
+
+    Ok(Json(SongsById { items, missing }))
+}
+
+async fn get_song(
+    State(state): State<AppState>,
+    song_id: SongId,
+    Query(query): Query<FieldsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let fields = Fields::parse(query.fields.as_deref())?;
+
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "get_song",
+        sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id = ?")
+            .bind(song_id)
+            .fetch_optional(&state.db),
+    )
+    .await?
+    .ok_or_else(|| AppError::not_found("Song not found"))?;
+
+    let tags = db::timed(
+        state.config.slow_query_ms,
+        "get_song_tags",
+        tags::song_tags(&state.db, song_id),
+    )
+    .await?;
 
-        query_builder.push(format!("{} LIKE '%{}%' ", key, value));
+    let etag = conditional::song_etag(&song, fields.names());
+    Ok((
+        [(header::ETAG, etag)],
+        Json(fields.project(&SongDetail { song, tags })),
+    ))
+}
+
+// Replaces the editable fields. If-Match takes the ETag of the full GET
+// /songs/:id representation; a ?fields= projection's tag never matches.
+// With it the write is a compare-and-swap: the UPDATE only matches if every
+// column still holds the values the ETag was checked against, so a
+// concurrent edit landing between the read and the write also ends in 412.
+// The ETag covers play_count as well, so a play recorded between the GET
+// and the PUT fails a metadata edit with 412 too, and the client re-reads.
+// The response is the same representation as the full GET, tags included.
+async fn update_song(
+    State(state): State<AppState>,
+    song_id: SongId,
+    headers: HeaderMap,
+    Json(payload): Json<Song>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(error) = payload.validation_error() {
+        return Err(AppError::unprocessable(error));
     }
 
-    let query = query_builder.join("");
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "update_song",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let current = sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id = ?")
+                    .bind(song_id)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or_else(|| AppError::not_found("Song not found"))?;
 
-    let song_results = sqlx::query_as::<_, Song>(&query)
-        .fetch_all(&state.db)
-        .await
-        .expect("Failed to fetch songs");
+                if !conditional::if_match(&headers, &conditional::song_etag(&current, None)) {
+                    return Err(precondition_failed());
+                }
+
+                let song = sqlx::query_as::<_, Song>(
+                    "UPDATE songs SET title = ?, artist = ?, genre = ?, duration_seconds = ?,
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+                    WHERE id = ? AND title = ? AND artist = ? AND genre = ?
+                    AND duration_seconds IS ? AND play_count = ? AND updated_at = ?
+                    RETURNING *",
+                )
+                .bind(text::normalize(&payload.title))
+                .bind(text::normalize(&payload.artist))
+                .bind(text::normalize(&payload.genre))
+                .bind(payload.duration_seconds)
+                .bind(song_id)
+                .bind(&current.title)
+                .bind(&current.artist)
+                .bind(&current.genre)
+                .bind(current.duration_seconds)
+                .bind(current.play_count)
+                .bind(&current.updated_at)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(precondition_failed)?;
+
+                let tags = tags::song_tags(&mut *tx, song_id).await?;
+                Ok(SongDetail { song, tags })
+            })
+        }),
+    )
+    .await?;
+
+    let etag = conditional::song_etag(&song.song, None);
+    Ok(([(header::ETAG, etag)], Json(song)))
+}
+
+fn precondition_failed() -> AppError {
+    AppError::new(
+        StatusCode::PRECONDITION_FAILED,
+        "The song has changed since it was read; fetch it again and retry",
+    )
+}
+
+async fn lookup_song(
+    State(state): State<AppState>,
+    Query(params): Query<LookupParams>,
+) -> Result<Response, AppError> {
+    let mut songs = db::timed(
+        state.config.slow_query_ms,
+        "lookup_song",
+        sqlx::query_as::<_, Song>(
+            "SELECT * FROM songs WHERE title = ? COLLATE NOCASE AND artist = ? COLLATE NOCASE",
+        )
+        .bind(text::normalize(&params.title))
+        .bind(text::normalize(&params.artist))
+        .fetch_all(&state.db),
+    )
+    .await?;
 
-    Json(song_results)
+    match songs.len() {
+        0 => Err(AppError::not_found("Song not found")),
+        1 => Ok(Json(songs.remove(0)).into_response()),
+        _ => Ok((
+            StatusCode::CONFLICT,
+            Json(LookupConflict {
+                error: "Multiple songs match this title and artist",
+                matches: songs,
+            }),
+        )
+            .into_response()),
+    }
 }
 
 async fn play_song(
     State(state): State<AppState>,
-    Path(params): Path<HashMap<String, String>>,
-) -> impl IntoResponse {
-    const ERROR_JSON: Json<SongNotFound> = Json(SongNotFound {
-        error: "Song not found",
+    song_id: SongId,
+    Query(params): Query<PlayParams>,
+) -> Result<Response, AppError> {
+    let (song, rank) = db::timed(
+        state.config.slow_query_ms,
+        "play_song",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                // SQLite silently turns an overflowing integer sum into a
+                // REAL, so the increment stops at i64::MAX instead.
+                let result = sqlx::query(
+                    "UPDATE songs SET play_count = play_count + 1,
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+                    WHERE id = ? AND play_count < ?",
+                )
+                .bind(song_id)
+                .bind(i64::MAX)
+                .execute(&mut *tx)
+                .await?;
+
+                if result.rows_affected() == 0 {
+                    let exists: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM songs WHERE id = ?")
+                        .bind(song_id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+
+                    return Err(match exists {
+                        Some(_) => AppError::conflict("Play count is already at its maximum"),
+                        None => AppError::not_found("Song not found"),
+                    });
+                }
+
+                sqlx::query("INSERT INTO plays (song_id) VALUES (?)")
+                    .bind(song_id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                let song = sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id = ?")
+                    .bind(song_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                if !params.with_rank {
+                    return Ok((song, None));
+                }
+
+                let (ahead,): (i64,) =
+                    sqlx::query_as("SELECT COUNT(*) FROM songs WHERE play_count > ?")
+                        .bind(song.play_count)
+                        .fetch_one(&mut *tx)
+                        .await?;
+
+                Ok((song, Some(ahead + 1)))
+            })
+        }),
+    )
+    .await?;
+
+    state.events.publish_play(PlayEvent {
+        song_id,
+        play_count: song.play_count,
+    });
+
+    match rank {
+        Some(rank) => Ok(Json(RankedPlay { song, rank }).into_response()),
+        None => Ok(Json(song).into_response()),
+    }
+}
+
+async fn unplay_song(
+    State(state): State<AppState>,
+    song_id: SongId,
+) -> Result<Json<Song>, AppError> {
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "unplay_song",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let song = sqlx::query_as::<_, Song>(
+                    "UPDATE songs SET play_count = MAX(play_count - 1, 0),
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE id = ? RETURNING *",
+                )
+                .bind(song_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| AppError::not_found("Song not found"))?;
+
+                // Undo the most recent play so windowed counts agree with
+                // the lifetime count.
+                sqlx::query(
+                    "DELETE FROM plays WHERE id =
+                    (SELECT id FROM plays WHERE song_id = ? ORDER BY id DESC LIMIT 1)",
+                )
+                .bind(song_id)
+                .execute(&mut *tx)
+                .await?;
+
+                Ok(song)
+            })
+        }),
+    )
+    .await?;
+
+    state.events.publish_play(PlayEvent {
+        song_id,
+        play_count: song.play_count,
     });
 
-    let song_id = match params.get("id") {
-        Some(id) => match id.parse::<i64>() {
-            Ok(id) => id,
-            Err(_) => return (StatusCode::BAD_REQUEST, ERROR_JSON.into_response()),
-        },
-        None => return (StatusCode::BAD_REQUEST, ERROR_JSON.into_response()),
+    Ok(Json(song))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Method, Request},
     };
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const ADMIN_TOKEN: &str = "test-admin-token";
+
+    async fn boot() -> (Router, SqlitePool) {
+        boot_with(|_| {}).await
+    }
+
+    async fn boot_with(configure: impl FnOnce(&mut Config)) -> (Router, SqlitePool) {
+        let mut config = Config {
+            database_url: "sqlite::memory:".to_string(),
+            summary_interval_secs: 0,
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            ..Config::from_env()
+        };
+        configure(&mut config);
+        let db = open_database(&config).await;
+        let (_, shutdown) = watch::channel(false);
+
+        (app(AppState::new(db.clone(), config, shutdown)), db)
+    }
+
+    async fn send(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN));
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        (status, body)
+    }
+
+    async fn add(app: &Router, title: &str, artist: &str, genre: &str) -> Value {
+        let song = json!({ "title": title, "artist": artist, "genre": genre });
+        let (status, body) = send(app, Method::POST, "/songs/new", Some(song)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+        body
+    }
+
+    #[tokio::test]
+    async fn boots_against_an_in_memory_database() {
+        let (app, _) = boot().await;
+
+        let song = add(&app, "Hey Jude", "The Beatles", "Rock").await;
+        let uri = format!("/songs/{}", song["id"]);
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["title"], "Hey Jude");
+    }
+
+    fn song(title: &str, artist: &str, genre: &str) -> Song {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "artist": artist,
+            "genre": genre,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn blank_fields_are_rejected() {
+        let errors = song(" ", "", "Rock").validation_errors();
+
+        assert_eq!(
+            errors,
+            ["title must not be empty", "artist must not be empty"]
+        );
+    }
 
-    let song = sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id = ?")
-        .bind(song_id)
-        .fetch_one(&state.db)
+    #[tokio::test]
+    async fn play_count_stops_at_i64_max() {
+        let (app, db) = boot().await;
+        let song = add(&app, "Hey Jude", "The Beatles", "Rock").await;
+        sqlx::query("UPDATE songs SET play_count = ? WHERE id = ?")
+            .bind(i64::MAX - 1)
+            .bind(song["id"].as_i64())
+            .execute(&db)
+            .await
+            .unwrap();
+        let uri = format!("/songs/play/{}", song["id"]);
+
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["play_count"], i64::MAX);
+
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "Play count is already at its maximum");
+    }
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        let errors = song(&"é".repeat(250), "The Beatles", "Rock").validation_errors();
+
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn oversized_title_is_a_clean_422() {
+        let (app, _) = boot().await;
+        let song = json!({ "title": "é".repeat(300), "artist": "The Beatles", "genre": "Rock" });
+
+        let (status, body) = send(&app, Method::POST, "/songs/new", Some(song)).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["error"],
+            "title is 300 characters long; the limit is 250"
+        );
+    }
+
+    #[tokio::test]
+    async fn search_matches_across_normalization_forms() {
+        let (app, _) = boot().await;
+        add(&app, "Halo", "Beyonce\u{301}", "Pop").await;
+
+        let uri = "/songs/search?artist=Beyonc%C3%A9";
+        let (status, body) = send(&app, Method::GET, uri, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 1, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn all_tokens_matches_reordered_words() {
+        let (app, _) = boot().await;
+        add(&app, "Hey Jude", "The Beatles", "Rock").await;
+
+        let (_, body) = send(&app, Method::GET, "/songs/search?title=jude%20hey", None).await;
+        assert_eq!(body["total"], 0, "{}", body);
+
+        let uri = "/songs/search?title=jude%20hey&match=all_tokens&highlight=true";
+        let (status, body) = send(&app, Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 1, "{}", body);
+        assert_eq!(
+            body["items"][0]["matches"],
+            json!([
+                { "field": "title", "start": 4, "len": 4 },
+                { "field": "title", "start": 0, "len": 3 },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn search_with_1000_filter_values_is_a_400() {
+        let (app, _) = boot().await;
+        let search = json!({ "exclude_genre": vec!["Rock"; 1000] });
+
+        let (status, body) = send(&app, Method::POST, "/songs/search", Some(search)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"],
+            "A search may use at most 50 filter values; this one has 1000"
+        );
+    }
+
+    #[tokio::test]
+    async fn long_query_strings_are_rejected_before_the_handler() {
+        let (app, _) = boot().await;
+
+        let uri = format!("/songs/search?title={}", "a".repeat(60_000));
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::URI_TOO_LONG);
+
+        let (status, body) = send(&app, Method::GET, "/songs/search?title=%FF", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Query string is not valid UTF-8");
+    }
+
+    // A URI this long cannot even be built as an http::Uri, so it goes over
+    // a real socket; hyper refuses it while reading the request head.
+    #[tokio::test]
+    async fn megabyte_query_string_is_rejected_quickly() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (app, _) = boot().await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, service).await });
+
+        let request = format!(
+            "GET /songs/search?title={} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "a".repeat(1 << 20)
+        );
+        let response = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            // The server may stop reading and close before the whole
+            // request is written.
+            stream.write_all(request.as_bytes()).await.ok();
+            let mut response = vec![0; 64];
+            let read = stream.read(&mut response).await.unwrap();
+            String::from_utf8_lossy(&response[..read]).into_owned()
+        })
         .await
-        .ok();
+        .expect("The oversized request was not rejected in time");
 
-    let mut song = match song {
-        Some(song) => song,
-        None => return (StatusCode::NOT_FOUND, ERROR_JSON.into_response()),
-    };
+        assert!(
+            response.starts_with("HTTP/1.1 431"),
+            "unexpected response: {}",
+            response
+        );
+    }
 
-    song.play_count += 1;
+    async fn plan(app: &Router, query: &str) -> String {
+        let uri = format!("/songs/search/explain?{}", query);
+        let (status, body) = send(app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
 
-    sqlx::query("UPDATE songs SET play_count = ? WHERE id = ?")
-        .bind(song.play_count)
-        .bind(song_id)
-        .execute(&state.db)
+        body.as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["detail"].as_str().unwrap())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    #[tokio::test]
+    async fn search_indexes_are_used_on_a_seeded_table() {
+        let (app, db) = boot().await;
+        sqlx::query(
+            "WITH RECURSIVE n (i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+            INSERT INTO songs (title, artist, genre, play_count)
+            SELECT 'Song ' || i, 'Artist ' || (i % 500), 'Genre ' || (i % 20), i FROM n",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let prefix = plan(&app, "artist=Artist%2042&prefix=true").await;
+        assert!(
+            prefix.contains("USING INDEX idx_songs_artist"),
+            "{}",
+            prefix
+        );
+
+        let exact = plan(&app, "genre=Genre%207&exact=true").await;
+        assert!(exact.contains("USING INDEX idx_songs_genre"), "{}", exact);
+
+        let sorted = plan(&app, "sort=play_count&order=desc").await;
+        assert!(
+            sorted.contains("USING INDEX idx_songs_play_count"),
+            "{}",
+            sorted
+        );
+
+        // A substring match cannot use an index, which is why prefix mode
+        // exists.
+        let contains = plan(&app, "artist=42").await;
+        assert!(!contains.contains("USING INDEX"), "{}", contains);
+    }
+
+    // Peak memory is bounded by the largest chunk held at once, which has
+    // to stay the same size however large the table grows.
+    #[tokio::test]
+    async fn export_streams_a_large_table_in_bounded_chunks() {
+        let (app, db) = boot().await;
+        sqlx::query(
+            "WITH RECURSIVE n (i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 20000)
+            INSERT INTO songs (title, artist, genre) SELECT 'Song ' || i, 'Artist', 'Genre' FROM n",
+        )
+        .execute(&db)
         .await
-        .expect("Failed to update play count");
+        .unwrap();
+
+        let (_, capped) = send(&app, Method::GET, "/songs/export?limit=1234", None).await;
+        assert_eq!(capped.as_array().unwrap().len(), 1234);
+
+        let request = Request::get("/songs/export").body(Body::empty()).unwrap();
+        let mut body = app.oneshot(request).await.unwrap().into_body();
+
+        let mut exported = Vec::new();
+        let mut chunks = 0;
+        let mut largest = 0;
+        while let Some(frame) = body.frame().await {
+            let chunk = frame.unwrap().into_data().unwrap();
+            chunks += 1;
+            largest = largest.max(chunk.len());
+            exported.extend_from_slice(&chunk);
+        }
+
+        let songs: Vec<Value> = serde_json::from_slice(&exported).unwrap();
+        assert_eq!(songs.len(), 20000);
+        assert!(chunks >= 40, "only {} chunks", chunks);
+        assert!(
+            largest * 20 < exported.len(),
+            "largest chunk was {} of {} bytes",
+            largest,
+            exported.len()
+        );
+    }
 
-    (StatusCode::OK, Json(song).into_response())
+    #[tokio::test]
+    async fn jsonapi_offset_at_i64_max_does_not_overflow() {
+        let (app, _) = boot().await;
+        add(&app, "Hey Jude", "The Beatles", "Rock").await;
+
+        let uri = format!("/jsonapi/songs?offset={}", i64::MAX);
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"], json!([]));
+        assert_eq!(body["links"]["next"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn modified_includes_writes_in_the_same_second() {
+        let (app, db) = boot().await;
+        add(&app, "Hey Jude", "The Beatles", "Rock").await;
+        add(&app, "Let It Be", "The Beatles", "Rock").await;
+        sqlx::query("UPDATE songs SET updated_at = '2024-11-29T10:00:00Z'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let uri = "/songs/modified?since=2024-11-29T10:00:00Z";
+        let (status, body) = send(&app, Method::GET, uri, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 2, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn incomplete_songs_are_paginated_and_versioned() {
+        let (app, db) = boot().await;
+        add(&app, "Hey Jude", "The Beatles", "Rock").await;
+        add(&app, "Let It Be", "The Beatles", "Rock").await;
+        sqlx::query("UPDATE songs SET genre = ''")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let request = Request::get("/songs/incomplete?limit=1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["x-page-limit"], "1");
+        assert_eq!(response.headers()["x-total-count"], "2");
+        assert_eq!(response.headers()["x-api-version"], "2");
+
+        let (_, body) = send(&app, Method::GET, "/songs/incomplete?api_version=1", None).await;
+        assert_eq!(body["missing"]["genre"], 2);
+        assert_eq!(body["items"].as_array().unwrap().len(), 2);
+        assert!(body.get("total").is_none(), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn search_with_1000_genre_params_is_a_json_400() {
+        let (app, _) = boot_with(|config| config.max_query_length = 1 << 16).await;
+
+        let uri = format!("/songs/search?{}", vec!["genre=Rock"; 1000].join("&"));
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "genre may only be given once");
+    }
 }
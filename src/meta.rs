@@ -0,0 +1,91 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::{error::AppError, AppState};
+
+const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+const LINKS: [(&str, &str); 4] = [
+    ("health", "/health"),
+    ("version", "/version"),
+    ("search", "/songs/search"),
+    ("events", "/events/plays"),
+];
+
+#[derive(Serialize)]
+struct Index {
+    service: &'static str,
+    version: &'static str,
+    links: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+pub struct Health {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct VersionInfo {
+    version: &'static str,
+    commit: &'static str,
+    migration: Option<i64>,
+}
+
+pub async fn index(headers: HeaderMap) -> Response {
+    let wants_html = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    if wants_html {
+        let links = LINKS
+            .iter()
+            .map(|(name, href)| format!("<li><a href=\"{}\">{}</a></li>", href, name))
+            .collect::<String>();
+
+        return Html(format!(
+            "<!doctype html><title>{0}</title><h1>{0} {1}</h1><ul>{2}</ul>",
+            SERVICE_NAME,
+            env!("CARGO_PKG_VERSION"),
+            links
+        ))
+        .into_response();
+    }
+
+    Json(Index {
+        service: SERVICE_NAME,
+        version: env!("CARGO_PKG_VERSION"),
+        links: LINKS
+            .iter()
+            .map(|(name, href)| (name.to_string(), serde_json::Value::from(*href)))
+            .collect(),
+    })
+    .into_response()
+}
+
+pub async fn health(State(state): State<AppState>) -> Result<Json<Health>, AppError> {
+    sqlx::query("SELECT 1")
+        .execute(&state.db)
+        .await
+        .map_err(|_| AppError::new(StatusCode::SERVICE_UNAVAILABLE, "Database unavailable"))?;
+
+    Ok(Json(Health { status: "ok" }))
+}
+
+pub async fn version(State(state): State<AppState>) -> Result<Json<VersionInfo>, AppError> {
+    let (migration,): (Option<i64>,) =
+        sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&state.db)
+            .await?;
+
+    Ok(Json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("GIT_COMMIT"),
+        migration,
+    }))
+}
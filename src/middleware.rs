@@ -0,0 +1,212 @@
+use std::{
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{header, HeaderMap, HeaderName, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+use tokio::sync::Semaphore;
+use tower_http::cors::{Any, CorsLayer};
+use tracing::warn;
+
+use crate::{client_ip::ClientIp, config::Config, error::AppError, AppState};
+
+pub fn cors(config: &Config) -> CorsLayer {
+    let expose_headers = config
+        .cors_expose_headers
+        .iter()
+        .map(|name| {
+            HeaderName::try_from(name.as_str()).expect("Invalid header in CORS_EXPOSE_HEADERS")
+        })
+        .collect::<Vec<_>>();
+
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(expose_headers)
+}
+
+pub async fn limit_query_string(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if let Some(query) = request.uri().query() {
+        if query.len() > state.config.max_query_length {
+            return Err(AppError::new(
+                StatusCode::URI_TOO_LONG,
+                format!(
+                    "Query string exceeds {} bytes",
+                    state.config.max_query_length
+                ),
+            ));
+        }
+
+        if percent_decode_str(query).decode_utf8().is_err() {
+            return Err(AppError::bad_request("Query string is not valid UTF-8"));
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+pub fn concurrency_limiter(config: &Config) -> Option<Arc<Semaphore>> {
+    (config.max_concurrent_requests > 0)
+        .then(|| Arc::new(Semaphore::new(config.max_concurrent_requests)))
+}
+
+// Requests beyond MAX_CONCURRENT_REQUESTS are turned away immediately
+// rather than queued, so a burst cannot pile up behind SQLite's single
+// writer.
+pub async fn limit_concurrency(
+    State(limiter): State<Option<Arc<Semaphore>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(limiter) = limiter else {
+        return Ok(next.run(request).await);
+    };
+
+    let _permit = limiter.try_acquire().map_err(|_| {
+        AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The server is handling too many requests; try again shortly",
+        )
+    })?;
+
+    Ok(next.run(request).await)
+}
+
+// Percentages are rolled independently, so a request can be delayed and
+// then still fail.
+pub async fn chaos(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let config = &state.config;
+
+    if roll() < config.chaos_delay_percent {
+        tokio::time::sleep(Duration::from_millis(config.chaos_delay_ms)).await;
+    }
+    if roll() < config.chaos_fail_percent {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Chaos mode failed this request",
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Each RandomState is seeded differently, which is random enough for
+// fault injection without pulling in a crate for it.
+fn roll() -> u64 {
+    RandomState::new().build_hasher().finish() % 100
+}
+
+pub async fn request_timeout(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let timeout_ms = state.config.request_timeout_ms;
+    if timeout_ms == 0 {
+        return Ok(next.run(request).await);
+    }
+
+    tokio::time::timeout(Duration::from_millis(timeout_ms), next.run(request))
+        .await
+        .map_err(|_| {
+            AppError::new(
+                StatusCode::REQUEST_TIMEOUT,
+                format!("Request exceeded the {} ms deadline", timeout_ms),
+            )
+        })
+}
+
+// Admin routes are closed unless ADMIN_TOKEN is set, and then require it
+// as a bearer token.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(token) = &state.config.admin_token else {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "Admin endpoints are disabled; set ADMIN_TOKEN to enable them",
+        ));
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if provided != Some(token.as_str()) {
+        let path = request
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or(request.uri().path(), |OriginalUri(uri)| uri.path());
+        warn!(%client_ip, path, "Rejected admin request");
+        return Err(AppError::new(
+            StatusCode::UNAUTHORIZED,
+            "A valid admin bearer token is required",
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Bodiless POSTs such as /songs/:id/unplay are let through; anything that
+// carries a body on a write method must declare it as JSON.
+pub async fn require_json(request: Request, next: Next) -> Result<Response, AppError> {
+    let writes = [Method::POST, Method::PUT, Method::PATCH];
+    if writes.contains(request.method())
+        && has_body(request.headers())
+        && !is_json(request.headers())
+    {
+        return Err(AppError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/json",
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+fn has_body(headers: &HeaderMap) -> bool {
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    length.is_some_and(|length| length > 0) || headers.contains_key(header::TRANSFER_ENCODING)
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    let Some((kind, subtype)) = essence.split_once('/') else {
+        return false;
+    };
+
+    kind.eq_ignore_ascii_case("application")
+        && (subtype.eq_ignore_ascii_case("json") || subtype.to_ascii_lowercase().ends_with("+json"))
+}
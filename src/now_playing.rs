@@ -0,0 +1,70 @@
+use axum::{extract::State, Json};
+use serde::Deserialize;
+
+use crate::{db, error::AppError, events::NowPlayingEvent, song_id::SongId, AppState, Song};
+
+#[derive(Deserialize)]
+pub struct SetNowPlaying {
+    song_id: Option<SongId>,
+}
+
+pub async fn get_now_playing(
+    State(state): State<AppState>,
+) -> Result<Json<Option<Song>>, AppError> {
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "get_now_playing",
+        sqlx::query_as::<_, Song>(
+            "SELECT songs.* FROM now_playing JOIN songs ON songs.id = now_playing.song_id
+            WHERE now_playing.slot = 1",
+        )
+        .fetch_optional(&state.db),
+    )
+    .await?;
+
+    Ok(Json(song))
+}
+
+// A null song_id clears the slot.
+pub async fn set_now_playing(
+    State(state): State<AppState>,
+    Json(payload): Json<SetNowPlaying>,
+) -> Result<Json<Option<Song>>, AppError> {
+    let song_id = payload.song_id;
+
+    let song = db::timed(
+        state.config.slow_query_ms,
+        "set_now_playing",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let song = match song_id {
+                    Some(song_id) => Some(
+                        sqlx::query_as::<_, Song>("SELECT * FROM songs WHERE id = ?")
+                            .bind(song_id)
+                            .fetch_optional(&mut *tx)
+                            .await?
+                            .ok_or_else(|| AppError::not_found("Song not found"))?,
+                    ),
+                    None => None,
+                };
+
+                sqlx::query(
+                    "UPDATE now_playing SET song_id = ?,
+                    updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE slot = 1",
+                )
+                .bind(song_id)
+                .execute(&mut *tx)
+                .await?;
+
+                Ok(song)
+            })
+        }),
+    )
+    .await?;
+
+    state
+        .events
+        .publish_now_playing(NowPlayingEvent { song_id });
+
+    Ok(Json(song))
+}
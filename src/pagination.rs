@@ -0,0 +1,74 @@
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::version::ApiVersion;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+// Sent on every paginated response so clients can tell when their limit
+// was clamped to MAX_PAGE_SIZE.
+pub const LIMIT_HEADER: &str = "x-page-limit";
+pub const TOTAL_HEADER: &str = "x-total-count";
+
+pub fn headers(limit: i64, total: i64) -> [(&'static str, String); 2] {
+    [
+        (LIMIT_HEADER, limit.to_string()),
+        (TOTAL_HEADER, total.to_string()),
+    ]
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct Pagination {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl Pagination {
+    pub fn limit(&self, max_page_size: i64) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, max_page_size)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+#[derive(Serialize)]
+pub struct PagedSongs<T> {
+    pub items: T,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+// The response for a plain list endpoint: a bare array for v1 and
+// PagedSongs for v2, with the page headers on both.
+pub fn paged<T: Serialize>(
+    version: ApiVersion,
+    songs: Vec<T>,
+    total: i64,
+    limit: i64,
+    offset: i64,
+) -> Response {
+    let headers = headers(limit, total);
+
+    match version {
+        ApiVersion::V1 => (version, headers, Json(songs)).into_response(),
+        ApiVersion::V2 => (
+            version,
+            headers,
+            Json(PagedSongs {
+                items: songs,
+                total,
+                limit,
+                offset,
+            }),
+        )
+            .into_response(),
+    }
+}
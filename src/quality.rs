@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::{
+    db,
+    error::AppError,
+    pagination::{self, PagedSongs, Pagination},
+    version::ApiVersion,
+    AppState, Song,
+};
+
+// Songs cannot lack a column outright, so "missing" means blank after
+// trimming. Album and year would join this list once they exist.
+const CHECKED_FIELDS: [&str; 3] = ["title", "artist", "genre"];
+
+#[derive(Deserialize)]
+pub struct IncompleteParams {
+    field: Option<String>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct MissingCounts {
+    title: i64,
+    artist: i64,
+    genre: i64,
+}
+
+// The missing counts sit beside the page: just the items for v1, and the
+// PagedSongs fields for v2.
+#[derive(Serialize)]
+pub struct IncompleteSongs<T> {
+    missing: MissingCounts,
+    #[serde(flatten)]
+    songs: T,
+}
+
+#[derive(Serialize)]
+pub struct Items {
+    items: Vec<Song>,
+}
+
+pub async fn incomplete_songs(
+    State(state): State<AppState>,
+    Query(params): Query<IncompleteParams>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let condition = match params.field.as_deref() {
+        None => CHECKED_FIELDS
+            .iter()
+            .map(|field| format!("TRIM({}) = ''", field))
+            .collect::<Vec<_>>()
+            .join(" OR "),
+        Some(field) if CHECKED_FIELDS.contains(&field) => format!("TRIM({}) = ''", field),
+        Some(_) => {
+            return Err(AppError::bad_request(format!(
+                "field must be one of {}",
+                CHECKED_FIELDS.join(", ")
+            )))
+        }
+    };
+
+    let limit = page.limit(state.config.max_page_size);
+    let offset = page.offset();
+
+    let (missing, total, items) = db::timed(
+        state.config.slow_query_ms,
+        "incomplete_songs",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let missing = sqlx::query_as::<_, MissingCounts>(
+                    "SELECT COALESCE(SUM(TRIM(title) = ''), 0) AS title,
+                    COALESCE(SUM(TRIM(artist) = ''), 0) AS artist,
+                    COALESCE(SUM(TRIM(genre) = ''), 0) AS genre FROM songs",
+                )
+                .fetch_one(&mut *tx)
+                .await?;
+
+                let (total,): (i64,) =
+                    sqlx::query_as(&format!("SELECT COUNT(*) FROM songs WHERE {}", condition))
+                        .fetch_one(&mut *tx)
+                        .await?;
+
+                let items = sqlx::query_as::<_, Song>(&format!(
+                    "SELECT * FROM songs WHERE {} ORDER BY id LIMIT ? OFFSET ?",
+                    condition
+                ))
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                Ok((missing, total, items))
+            })
+        }),
+    )
+    .await?;
+
+    let headers = pagination::headers(limit, total);
+
+    Ok(match version {
+        ApiVersion::V1 => (
+            version,
+            headers,
+            Json(IncompleteSongs {
+                missing,
+                songs: Items { items },
+            }),
+        )
+            .into_response(),
+        ApiVersion::V2 => (
+            version,
+            headers,
+            Json(IncompleteSongs {
+                missing,
+                songs: PagedSongs {
+                    items,
+                    total,
+                    limit,
+                    offset,
+                },
+            }),
+        )
+            .into_response(),
+    })
+}
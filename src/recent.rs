@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Query, State},
+    response::Response,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, QueryBuilder, Sqlite};
+
+use crate::{
+    db,
+    error::AppError,
+    pagination::{self, Pagination},
+    timestamp,
+    version::ApiVersion,
+    AppState, Song,
+};
+
+const DEFAULT_MODIFIED_WINDOW_HOURS: i64 = 24;
+
+#[derive(Deserialize)]
+pub struct WindowParams {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SinceParams {
+    since: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct PlayedSong {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    song: Song,
+    plays_in_window: i64,
+    last_played_at: String,
+}
+
+pub async fn added_songs(
+    State(state): State<AppState>,
+    Query(window): Query<WindowParams>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let (from, to) = timestamp::parse_window(window.from.as_deref(), window.to.as_deref())?;
+
+    let window = [(">=", from), ("<", to)];
+    list_newest(&state, "added_songs", "created_at", &window, page, version).await
+}
+
+// Sync clients pass the newest updated_at they have seen and pull
+// everything changed at or after it. updated_at is stored to the second,
+// so a strict > would skip a write landing later in that same second;
+// the rows from that second come back again and clients dedupe by id.
+pub async fn modified_songs(
+    State(state): State<AppState>,
+    Query(params): Query<SinceParams>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let since = match params.since.as_deref() {
+        Some(since) => timestamp::parse("since", since)?,
+        None => Utc::now() - Duration::hours(DEFAULT_MODIFIED_WINDOW_HOURS),
+    };
+
+    let window = [(">=", Some(since))];
+    list_newest(
+        &state,
+        "modified_songs",
+        "updated_at",
+        &window,
+        page,
+        version,
+    )
+    .await
+}
+
+// Counts come from the plays history, so they cover only the window
+// rather than the song's lifetime play_count.
+pub async fn played_songs(
+    State(state): State<AppState>,
+    Query(window): Query<WindowParams>,
+    Query(page): Query<Pagination>,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let (from, to) = timestamp::parse_window(window.from.as_deref(), window.to.as_deref())?;
+    let bounds = [(">=", from), ("<", to)];
+    let limit = page.limit(state.config.max_page_size);
+
+    let mut count = QueryBuilder::new("SELECT COUNT(DISTINCT song_id) FROM plays WHERE 1 = 1");
+    push_window(&mut count, "played_at", &bounds);
+
+    let mut builder = QueryBuilder::new(
+        "SELECT songs.*, COUNT(*) AS plays_in_window, MAX(plays.played_at) AS last_played_at
+        FROM plays JOIN songs ON songs.id = plays.song_id WHERE 1 = 1",
+    );
+    push_window(&mut builder, "plays.played_at", &bounds);
+    builder.push(" GROUP BY songs.id ORDER BY last_played_at DESC, songs.id DESC LIMIT ");
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(page.offset());
+
+    let (total, songs) = db::timed(
+        state.config.slow_query_ms,
+        "played_songs",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let (total,): (i64,) = count.build_query_as().fetch_one(&mut *tx).await?;
+                let songs = builder
+                    .build_query_as::<PlayedSong>()
+                    .fetch_all(&mut *tx)
+                    .await?;
+                Ok((total, songs))
+            })
+        }),
+    )
+    .await?;
+
+    Ok(pagination::paged(
+        version,
+        songs,
+        total,
+        limit,
+        page.offset(),
+    ))
+}
+
+fn push_window(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    column: &str,
+    bounds: &[(&str, Option<DateTime<Utc>>)],
+) {
+    for (operator, bound) in bounds {
+        if let Some(bound) = bound {
+            builder.push(format!(" AND {} {} ", column, operator));
+            builder.push_bind(timestamp::to_db(*bound));
+        }
+    }
+}
+
+async fn list_newest(
+    state: &AppState,
+    name: &str,
+    column: &'static str,
+    bounds: &[(&str, Option<DateTime<Utc>>)],
+    page: Pagination,
+    version: ApiVersion,
+) -> Result<Response, AppError> {
+    let limit = page.limit(state.config.max_page_size);
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM songs WHERE 1 = 1");
+    push_window(&mut count, column, bounds);
+
+    let mut builder = QueryBuilder::new("SELECT * FROM songs WHERE 1 = 1");
+    push_window(&mut builder, column, bounds);
+    builder.push(format!(" ORDER BY {} DESC, id DESC LIMIT ", column));
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(page.offset());
+
+    let (total, songs) = db::timed(
+        state.config.slow_query_ms,
+        name,
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let (total,): (i64,) = count.build_query_as().fetch_one(&mut *tx).await?;
+                let songs = builder.build_query_as::<Song>().fetch_all(&mut *tx).await?;
+                Ok((total, songs))
+            })
+        }),
+    )
+    .await?;
+
+    Ok(pagination::paged(
+        version,
+        songs,
+        total,
+        limit,
+        page.offset(),
+    ))
+}
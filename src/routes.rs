@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{http::Uri, routing::MethodRouter, Router};
+
+use crate::{error::AppError, AppState};
+
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+// Wraps the router so every registered path is also recorded, which keeps
+// the list the 404 fallback suggests from in step with the real routes.
+pub struct Routes {
+    router: Router<AppState>,
+    paths: Vec<String>,
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Self {
+            router: Router::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    pub fn route(mut self, path: &str, method_router: MethodRouter<AppState>) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.paths.push(path.to_string());
+        self
+    }
+
+    pub fn nest(mut self, prefix: &str, routes: Routes) -> Self {
+        self.router = self.router.nest(prefix, routes.router);
+        self.paths.extend(
+            routes
+                .paths
+                .iter()
+                .map(|path| format!("{}{}", prefix, path)),
+        );
+        self
+    }
+
+    pub fn map(mut self, f: impl FnOnce(Router<AppState>) -> Router<AppState>) -> Self {
+        self.router = f(self.router);
+        self
+    }
+
+    pub fn into_router(self, suggest: bool) -> Router<AppState> {
+        let paths: Arc<[String]> = self.paths.into();
+
+        self.router.fallback(move |uri: Uri| {
+            let paths = paths.clone();
+            async move { not_found(&uri, suggest.then_some(&*paths)) }
+        })
+    }
+}
+
+fn not_found(uri: &Uri, paths: Option<&[String]>) -> AppError {
+    let path = uri.path();
+
+    match paths.and_then(|paths| closest(path, paths)) {
+        Some(suggestion) => AppError::not_found(format!(
+            "No route for {}; did you mean {}?",
+            path, suggestion
+        )),
+        None => AppError::not_found(format!("No route for {}", path)),
+    }
+}
+
+fn closest<'a>(path: &str, paths: &'a [String]) -> Option<&'a str> {
+    paths
+        .iter()
+        .map(|candidate| (distance(path, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+// With the same number of segments, a :param segment matches anything, so
+// /songs/12/tag is one edit from /songs/:id/tags.
+fn distance(path: &str, route: &str) -> usize {
+    let path_segments = path.split('/').collect::<Vec<_>>();
+    let route_segments = route.split('/').collect::<Vec<_>>();
+
+    if path_segments.len() != route_segments.len() {
+        return levenshtein(path, route);
+    }
+
+    path_segments
+        .iter()
+        .zip(&route_segments)
+        .map(|(segment, pattern)| {
+            if pattern.starts_with(':') {
+                0
+            } else {
+                levenshtein(segment, pattern)
+            }
+        })
+        .sum()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
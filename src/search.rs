@@ -0,0 +1,750 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::Query as MultiQuery;
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{prelude::FromRow, QueryBuilder, Sqlite, SqliteConnection};
+use tracing::{info, info_span, Instrument};
+
+use crate::{
+    conditional, db,
+    error::AppError,
+    fields::{Fields, FieldsQuery},
+    highlight,
+    pagination::{self, PagedSongs, Pagination},
+    tags, text,
+    version::ApiVersion,
+    AppState, Song,
+};
+
+pub const SORT_COLUMNS: [&str; 6] = ["id", "title", "artist", "genre", "play_count", TRENDING];
+
+const TRENDING: &str = "trending";
+
+// Lifetime plays still count under sort=trending, but only lightly, so a
+// burst of recent plays outranks a long history.
+const TRENDING_LIFETIME_WEIGHT: f64 = 0.1;
+
+const QUERY_PARAMS: [&str; 18] = [
+    "title",
+    "artist",
+    "genre",
+    "tag",
+    "exclude_genre",
+    "exclude_artist",
+    "exact",
+    "fuzzy",
+    "prefix",
+    "match",
+    "highlight",
+    "echo",
+    "sort",
+    "order",
+    "limit",
+    "offset",
+    "fields",
+    "api_version",
+];
+
+// Only the exclusions take several values.
+const MULTI_VALUE_PARAMS: [&str; 2] = ["exclude_genre", "exclude_artist"];
+
+// MultiQuery rejects a repeated single-value parameter with a plain-text
+// "unsupported value" that does not name it, so repeats are caught first
+// and every failure comes back in the JSON error envelope.
+pub struct SearchQuery(pub SearchParams);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SearchQuery {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(pairs) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map_err(|rejection| AppError::bad_request(rejection.body_text()))?;
+
+        let mut seen = Vec::new();
+        for (key, _) in &pairs {
+            if !QUERY_PARAMS.contains(&key.as_str()) || MULTI_VALUE_PARAMS.contains(&key.as_str()) {
+                continue;
+            }
+            if seen.contains(&key) {
+                return Err(AppError::bad_request(format!(
+                    "{} may only be given once",
+                    key
+                )));
+            }
+            seen.push(key);
+        }
+
+        let MultiQuery(params) = MultiQuery::<SearchParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| {
+                AppError::bad_request(format!("Invalid search parameters: {}", rejection))
+            })?;
+        Ok(Self(params))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchRequest {
+    #[serde(flatten)]
+    params: SearchParams,
+    #[serde(flatten)]
+    page: Pagination,
+    fields: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub genre: Option<String>,
+    pub tag: Option<String>,
+
+    #[serde(default)]
+    pub exclude_genre: Vec<String>,
+    #[serde(default)]
+    pub exclude_artist: Vec<String>,
+
+    #[serde(default)]
+    pub exact: bool,
+    #[serde(default)]
+    pub fuzzy: bool,
+    #[serde(default)]
+    pub prefix: bool,
+    #[serde(rename = "match")]
+    pub match_mode: Option<String>,
+
+    #[serde(default)]
+    pub highlight: bool,
+    #[serde(default)]
+    pub echo: bool,
+
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+impl SearchParams {
+    pub fn validate(&self, max_filters: usize) -> Result<(), AppError> {
+        let filters = self.filters().count()
+            + usize::from(self.tag.is_some())
+            + self.exclude_genre.len()
+            + self.exclude_artist.len();
+        if filters > max_filters {
+            return Err(AppError::bad_request(format!(
+                "A search may use at most {} filter values; this one has {}",
+                max_filters, filters
+            )));
+        }
+
+        let modes = [self.exact, self.fuzzy, self.prefix];
+        if modes.into_iter().filter(|mode| *mode).count() > 1 {
+            return Err(AppError::bad_request(
+                "Only one of exact, fuzzy and prefix can be set",
+            ));
+        }
+
+        match self.match_mode.as_deref() {
+            None | Some("phrase") => {}
+            Some("all_tokens") if !self.exact && !self.fuzzy && !self.prefix => {}
+            Some("all_tokens") => {
+                return Err(AppError::bad_request(
+                    "match=all_tokens cannot be combined with exact, fuzzy or prefix",
+                ));
+            }
+            Some(_) => return Err(AppError::bad_request("match must be phrase or all_tokens")),
+        }
+
+        if let Some(tag) = &self.tag {
+            tags::normalize(tag)?;
+        }
+
+        if let Some(sort) = &self.sort {
+            if !SORT_COLUMNS.contains(&sort.as_str()) {
+                return Err(AppError::bad_request(format!(
+                    "sort must be one of {}",
+                    SORT_COLUMNS.join(", ")
+                )));
+            }
+        }
+
+        match (&self.sort, self.order.as_deref()) {
+            (None, Some(_)) => Err(AppError::bad_request("order requires sort")),
+            (Some(_), Some(order)) if order != "asc" && order != "desc" => {
+                Err(AppError::bad_request("order must be asc or desc"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn filters(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("genre", &self.genre),
+        ]
+        .into_iter()
+        .filter_map(|(column, value)| value.as_deref().map(|value| (column, value)))
+    }
+
+    // Whether anything would narrow the result, so bulk writes can refuse
+    // to touch the whole library by accident.
+    pub fn has_filter(&self) -> bool {
+        self.filters().any(|(_, value)| !value.trim().is_empty())
+            || self.tag.is_some()
+            || self.exclusions().next().is_some()
+    }
+
+    pub fn all_tokens(&self) -> bool {
+        self.match_mode.as_deref() == Some("all_tokens")
+    }
+
+    fn exclusions(&self) -> impl Iterator<Item = (&'static str, Vec<&str>)> {
+        [
+            ("genre", &self.exclude_genre),
+            ("artist", &self.exclude_artist),
+        ]
+        .into_iter()
+        .map(|(column, values)| {
+            let values = values
+                .iter()
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .collect::<Vec<_>>();
+            (column, values)
+        })
+        .filter(|(_, values)| !values.is_empty())
+    }
+
+    pub fn push_where(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        let mut first = true;
+        let mut push_clause = |builder: &mut QueryBuilder<'_, Sqlite>| {
+            builder.push(if first { " WHERE " } else { " AND " });
+            first = false;
+        };
+
+        if let Some(tag) = self
+            .tag
+            .as_deref()
+            .and_then(|tag| tags::normalize(tag).ok())
+        {
+            push_clause(builder);
+            builder.push(
+                "id IN (SELECT song_tags.song_id FROM song_tags
+                JOIN tags ON tags.id = song_tags.tag_id WHERE tags.name = ",
+            );
+            builder.push_bind(tag);
+            builder.push(")");
+        }
+
+        for (column, values) in self.exclusions() {
+            push_clause(builder);
+            builder.push(format!("{} COLLATE NOCASE NOT IN (", column));
+            let mut separated = builder.separated(", ");
+            for value in values {
+                separated.push_bind(text::normalize(value));
+            }
+            separated.push_unseparated(")");
+        }
+
+        for (column, value) in self.filters() {
+            let value = &text::normalize(value);
+            push_clause(builder);
+
+            // Every whitespace-separated token must appear somewhere in the
+            // field, in any order.
+            if self.all_tokens() {
+                builder.push("(1 = 1");
+                for token in value.split_whitespace() {
+                    builder.push(" AND ");
+                    builder.push(column);
+                    builder.push(" LIKE ");
+                    builder.push_bind(format!("%{}%", escape_like(token)));
+                    builder.push(" ESCAPE '\\'");
+                }
+                builder.push(")");
+                continue;
+            }
+
+            builder.push(column);
+
+            if self.exact {
+                builder.push(" = ");
+                builder.push_bind(value.to_string());
+                builder.push(" COLLATE NOCASE");
+                continue;
+            }
+
+            let pattern = if self.fuzzy {
+                let pattern = value
+                    .chars()
+                    .map(|c| escape_like(&c.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("%");
+                format!("%{}%", pattern)
+            } else if self.prefix {
+                format!("{}%", escape_like(value))
+            } else {
+                format!("%{}%", escape_like(value))
+            };
+            builder.push(" LIKE ");
+            builder.push_bind(pattern);
+            builder.push(" ESCAPE '\\'");
+        }
+    }
+
+    fn mode(&self) -> &'static str {
+        if self.exact {
+            "exact"
+        } else if self.fuzzy {
+            "fuzzy"
+        } else if self.prefix {
+            "prefix"
+        } else if self.all_tokens() {
+            "all_tokens"
+        } else {
+            "contains"
+        }
+    }
+
+    // Trending defaults to hottest first; every other sort to ascending.
+    fn order(&self) -> Option<&'static str> {
+        let sort = self.sort.as_ref()?;
+        Some(match self.order.as_deref() {
+            Some("desc") => "desc",
+            Some(_) => "asc",
+            None if sort == TRENDING => "desc",
+            None => "asc",
+        })
+    }
+
+    fn trending(&self) -> bool {
+        self.sort.as_deref() == Some(TRENDING)
+    }
+
+    // Under sort=trending each play in the last window_days scores 1 when
+    // fresh and decays linearly to 0 at the edge of the window. The sum is
+    // exposed as trending_score.
+    pub fn select(&self, window_days: u64) -> String {
+        if !self.trending() {
+            return "SELECT * FROM songs".to_string();
+        }
+
+        format!(
+            "SELECT *, COALESCE((SELECT SUM(1.0 - (julianday('now') - julianday(plays.played_at)) / {0})
+            FROM plays WHERE plays.song_id = songs.id
+            AND plays.played_at >= strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-{0} days')), 0)
+            + play_count * {1} AS trending_score FROM songs",
+            window_days,
+            TRENDING_LIFETIME_WEIGHT
+        )
+    }
+
+    pub fn push_order_by(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        if let (Some(sort), Some(order)) = (&self.sort, self.order()) {
+            if self.trending() {
+                builder.push(format!(
+                    " ORDER BY trending_score {}, id",
+                    order.to_uppercase()
+                ));
+            } else {
+                builder.push(format!(" ORDER BY {} {}", sort, order.to_uppercase()));
+            }
+        }
+    }
+}
+
+// What the server actually ran the search with, echoed back under
+// ?echo=true so clients can see normalized terms and clamped limits.
+#[derive(Serialize)]
+struct Applied<'a> {
+    title: Option<String>,
+    artist: Option<String>,
+    genre: Option<String>,
+    tag: Option<String>,
+    exclude_genre: Vec<String>,
+    exclude_artist: Vec<String>,
+    mode: &'static str,
+    sort: Option<&'a str>,
+    order: Option<&'static str>,
+    limit: i64,
+    offset: i64,
+    fields: Option<&'a [String]>,
+    highlight: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ignored: Vec<String>,
+}
+
+impl<'a> Applied<'a> {
+    fn new(params: &'a SearchParams, fields: &'a Fields, limit: i64, offset: i64) -> Self {
+        let normalized = |value: &Option<String>| value.as_deref().map(text::normalize);
+        let excluded = |values: &[String]| {
+            values
+                .iter()
+                .map(|value| text::normalize(value.trim()))
+                .filter(|value| !value.is_empty())
+                .collect()
+        };
+
+        Self {
+            title: normalized(&params.title),
+            artist: normalized(&params.artist),
+            genre: normalized(&params.genre),
+            tag: params
+                .tag
+                .as_deref()
+                .and_then(|tag| tags::normalize(tag).ok()),
+            exclude_genre: excluded(&params.exclude_genre),
+            exclude_artist: excluded(&params.exclude_artist),
+            mode: params.mode(),
+            sort: params.sort.as_deref(),
+            order: params.order(),
+            limit,
+            offset,
+            fields: fields.names(),
+            highlight: params.highlight,
+            ignored: Vec::new(),
+        }
+    }
+}
+
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub async fn search_song(
+    State(state): State<AppState>,
+    SearchQuery(params): SearchQuery,
+    Query(query): Query<FieldsQuery>,
+    Query(page): Query<Pagination>,
+    Query(raw): Query<Vec<(String, String)>>,
+    version: ApiVersion,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    params.validate(state.config.max_search_filters)?;
+    let fields = Fields::parse(query.fields.as_deref())?;
+
+    let last_modified = conditional::library_last_modified(&state.db).await?;
+    if let Some(last_modified) = last_modified {
+        if conditional::not_modified_since(&headers, last_modified) {
+            return Ok(StatusCode::NOT_MODIFIED.into_response());
+        }
+    }
+
+    let limit = page.limit(state.config.max_page_size);
+    let (total, songs) = fetch_songs(&state, &params, limit, page.offset()).await?;
+
+    let applied = params.echo.then(|| {
+        let mut applied = Applied::new(&params, &fields, limit, page.offset());
+        for (key, _) in raw {
+            if !QUERY_PARAMS.contains(&key.as_str()) && !applied.ignored.contains(&key) {
+                applied.ignored.push(key);
+            }
+        }
+        applied
+    });
+    let body = shape(
+        version,
+        render(&fields, &params, &songs),
+        applied,
+        total,
+        limit,
+        page.offset(),
+    );
+
+    let mut response = (
+        version,
+        [
+            (pagination::LIMIT_HEADER, limit.to_string()),
+            (pagination::TOTAL_HEADER, total.to_string()),
+        ],
+        Json(body),
+    )
+        .into_response();
+    if let Some(last_modified) = last_modified {
+        response
+            .headers_mut()
+            .insert(header::LAST_MODIFIED, conditional::http_date(last_modified));
+    }
+
+    Ok(response)
+}
+
+pub async fn search_song_post(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(request): Json<SearchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    request.params.validate(state.config.max_search_filters)?;
+    let fields = Fields::parse(request.fields.as_deref())?;
+
+    let limit = request.page.limit(state.config.max_page_size);
+    let (total, songs) = fetch_songs(&state, &request.params, limit, request.page.offset()).await?;
+
+    let applied = request
+        .params
+        .echo
+        .then(|| Applied::new(&request.params, &fields, limit, request.page.offset()));
+    let body = shape(
+        version,
+        render(&fields, &request.params, &songs),
+        applied,
+        total,
+        limit,
+        request.page.offset(),
+    );
+
+    Ok((
+        version,
+        [
+            (pagination::LIMIT_HEADER, limit.to_string()),
+            (pagination::TOTAL_HEADER, total.to_string()),
+        ],
+        Json(body),
+    ))
+}
+
+// v1 keeps the bare array, or {applied, items} under ?echo=true; v2
+// always answers with a PagedSongs object and adds applied to it.
+fn shape(
+    version: ApiVersion,
+    items: Value,
+    applied: Option<Applied>,
+    total: i64,
+    limit: i64,
+    offset: i64,
+) -> Value {
+    let applied = applied
+        .map(|applied| serde_json::to_value(applied).expect("Failed to serialize applied filters"));
+
+    if version == ApiVersion::V1 {
+        return match applied {
+            Some(applied) => serde_json::json!({ "applied": applied, "items": items }),
+            None => items,
+        };
+    }
+
+    let mut body = serde_json::to_value(PagedSongs {
+        items,
+        total,
+        limit,
+        offset,
+    })
+    .expect("Failed to serialize page");
+    if let (Some(applied), Value::Object(object)) = (applied, &mut body) {
+        object.insert("applied".to_string(), applied);
+    }
+    body
+}
+
+fn render(fields: &Fields, params: &SearchParams, songs: &[Song]) -> Value {
+    if !params.highlight {
+        return fields.project_all(songs);
+    }
+
+    Value::Array(
+        songs
+            .iter()
+            .map(|song| {
+                let mut value = fields.project(song);
+                if let Value::Object(object) = &mut value {
+                    object.insert(
+                        "matches".to_string(),
+                        serde_json::to_value(highlight::matches(params, song))
+                            .expect("Failed to serialize matches"),
+                    );
+                }
+                value
+            })
+            .collect(),
+    )
+}
+
+// The count and the page run in one read transaction. In WAL mode SQLite
+// pins a snapshot at the transaction's first read, so writes committed in
+// between are invisible to both; under the fast profile's rollback journal
+// the shared lock instead holds writers off until the page is read. Either
+// way the total always matches the page.
+pub async fn fetch_songs(
+    state: &AppState,
+    params: &SearchParams,
+    limit: i64,
+    offset: i64,
+) -> Result<(i64, Vec<Song>), AppError> {
+    state.search_stats.record(params);
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM songs");
+    params.push_where(&mut count);
+
+    let mut builder = page_query(
+        &params.select(state.config.trending_window_days),
+        params,
+        limit,
+        offset,
+    );
+
+    let page = db::timed(
+        state.config.slow_query_ms,
+        "search_song",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let (total,): (i64,) = count.build_query_as().fetch_one(&mut *tx).await?;
+                let songs = stream_songs(tx, &mut builder).await?;
+                Ok((total, songs))
+            })
+        }),
+    )
+    .instrument(info_span!("search_song", filters = ?params))
+    .await?;
+
+    Ok(page)
+}
+
+fn page_query(
+    select: &str,
+    params: &SearchParams,
+    limit: i64,
+    offset: i64,
+) -> QueryBuilder<'static, Sqlite> {
+    let mut builder = QueryBuilder::new(select);
+    params.push_where(&mut builder);
+    params.push_order_by(&mut builder);
+    builder.push(" LIMIT ");
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+    builder
+}
+
+#[derive(FromRow, Serialize)]
+pub struct PlanRow {
+    id: i64,
+    parent: i64,
+    detail: String,
+}
+
+pub async fn explain_search(
+    State(state): State<AppState>,
+    SearchQuery(params): SearchQuery,
+    Query(page): Query<Pagination>,
+) -> Result<Json<Vec<PlanRow>>, AppError> {
+    params.validate(state.config.max_search_filters)?;
+
+    let limit = page.limit(state.config.max_page_size);
+    let mut builder = page_query(
+        &format!(
+            "EXPLAIN QUERY PLAN {}",
+            params.select(state.config.trending_window_days)
+        ),
+        &params,
+        limit,
+        page.offset(),
+    );
+
+    let plan = db::timed(
+        state.config.slow_query_ms,
+        "explain_search",
+        builder.build_query_as::<PlanRow>().fetch_all(&state.db),
+    )
+    .await?;
+
+    Ok(Json(plan))
+}
+
+// Axum drops the handler future when the client disconnects. Rows are
+// pulled one at a time, so dropping the stream stops SQLite's worker at
+// the next row instead of letting it finish an abandoned scan.
+async fn stream_songs(
+    conn: &mut SqliteConnection,
+    builder: &mut QueryBuilder<'_, Sqlite>,
+) -> Result<Vec<Song>, sqlx::Error> {
+    let mut guard = CancelGuard { finished: false };
+    let mut rows = builder.build_query_as::<Song>().fetch(conn);
+
+    let mut songs = Vec::new();
+    while let Some(song) = rows.try_next().await? {
+        songs.push(song);
+    }
+
+    guard.finished = true;
+    Ok(songs)
+}
+
+struct CancelGuard {
+    finished: bool,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            info!("Search cancelled before completion; stopping the query");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[test]
+    fn too_many_filter_values_are_rejected() {
+        let params = SearchParams {
+            exclude_genre: vec!["Rock".to_string(); 1000],
+            ..SearchParams::default()
+        };
+
+        let error = params.validate(50).unwrap_err();
+
+        assert_eq!(
+            error.message(),
+            "A search may use at most 50 filter values; this one has 1000"
+        );
+    }
+
+    // The client disconnecting is simulated by a timeout dropping the query
+    // mid-stream. With a single connection, the follow-up query can only
+    // run once the endless one has stopped and the connection is back.
+    #[tokio::test]
+    async fn dropped_search_stops_the_query() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let search = db::with_tx(&pool, |tx| {
+            Box::pin(async move {
+                let mut endless = QueryBuilder::new(
+                    "WITH RECURSIVE n (i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n)
+                    SELECT i AS id, 'Song' AS title, 'Artist' AS artist, 'Genre' AS genre,
+                    0 AS play_count, NULL AS duration_seconds, '' AS updated_at,
+                    '' AS created_at FROM n",
+                );
+                Ok(stream_songs(tx, &mut endless).await?)
+            })
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(200), search)
+            .await
+            .is_err());
+
+        let next = sqlx::query_as::<_, (i64,)>("SELECT 1").fetch_one(&pool);
+        let (one,) = tokio::time::timeout(Duration::from_secs(5), next)
+            .await
+            .expect("The abandoned query kept the connection busy")
+            .unwrap();
+        assert_eq!(one, 1);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::search::{SearchParams, SORT_COLUMNS};
+
+const FILTERS: [&str; 6] = [
+    "title",
+    "artist",
+    "genre",
+    "tag",
+    "exclude_genre",
+    "exclude_artist",
+];
+
+// In-memory tally of which columns searches filter and sort on, to guide
+// which indexes are worth adding. Counts start from zero on every restart.
+pub struct SearchStats {
+    since: String,
+    searches: AtomicU64,
+    filters: [AtomicU64; FILTERS.len()],
+    sorts: [AtomicU64; SORT_COLUMNS.len()],
+}
+
+#[derive(Serialize)]
+pub struct SearchStatsReport {
+    since: String,
+    searches: u64,
+    filters: Vec<ColumnCount>,
+    sorts: Vec<ColumnCount>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnCount {
+    column: &'static str,
+    searches: u64,
+}
+
+impl SearchStats {
+    pub fn new() -> Self {
+        Self {
+            since: Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            searches: AtomicU64::new(0),
+            filters: Default::default(),
+            sorts: Default::default(),
+        }
+    }
+
+    pub fn record(&self, params: &SearchParams) {
+        self.searches.fetch_add(1, Ordering::Relaxed);
+
+        let used = [
+            params.title.is_some(),
+            params.artist.is_some(),
+            params.genre.is_some(),
+            params.tag.is_some(),
+            !params.exclude_genre.is_empty(),
+            !params.exclude_artist.is_empty(),
+        ];
+        for (counter, used) in self.filters.iter().zip(used) {
+            if used {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if let Some(index) = params
+            .sort
+            .as_deref()
+            .and_then(|sort| SORT_COLUMNS.iter().position(|column| *column == sort))
+        {
+            self.sorts[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Most-used columns first.
+    pub fn report(&self) -> SearchStatsReport {
+        let counts = |names: &[&'static str], counters: &[AtomicU64]| {
+            let mut counts = names
+                .iter()
+                .zip(counters)
+                .map(|(column, counter)| ColumnCount {
+                    column,
+                    searches: counter.load(Ordering::Relaxed),
+                })
+                .collect::<Vec<_>>();
+            counts.sort_by_key(|count| Reverse(count.searches));
+            counts
+        };
+
+        SearchStatsReport {
+            since: self.since.clone(),
+            searches: self.searches.load(Ordering::Relaxed),
+            filters: counts(&FILTERS, &self.filters),
+            sorts: counts(&SORT_COLUMNS, &self.sorts),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::error::AppError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, sqlx::Type)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct SongId(pub i64);
+
+impl SongId {
+    pub fn new(id: i64) -> Result<Self, AppError> {
+        if id > 0 {
+            Ok(Self(id))
+        } else {
+            Err(invalid_song_id())
+        }
+    }
+}
+
+impl FromStr for SongId {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .parse::<i64>()
+            .map_err(|_| invalid_song_id())
+            .and_then(Self::new)
+    }
+}
+
+impl fmt::Display for SongId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<'de> Deserialize<'de> for SongId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SongIdVisitor;
+
+        impl de::Visitor<'_> for SongIdVisitor {
+            type Value = SongId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a positive song id")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<SongId, E> {
+                SongId::new(v).map_err(|_| E::custom(INVALID_SONG_ID))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<SongId, E> {
+                i64::try_from(v)
+                    .map_err(|_| E::custom(INVALID_SONG_ID))
+                    .and_then(|v| self.visit_i64(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<SongId, E> {
+                v.parse().map_err(|_| E::custom(INVALID_SONG_ID))
+            }
+        }
+
+        deserializer.deserialize_any(SongIdVisitor)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SongId {
+    type Rejection = AppError;
+
+    // Reads the :id segment by name so routes can carry further parameters
+    // such as /songs/:id/tags/:tag.
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| invalid_song_id())?;
+
+        params.get("id").ok_or_else(invalid_song_id)?.parse()
+    }
+}
+
+const INVALID_SONG_ID: &str = "Song id must be a positive integer";
+
+fn invalid_song_id() -> AppError {
+    AppError::bad_request(INVALID_SONG_ID)
+}
@@ -0,0 +1,253 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, QueryBuilder};
+
+use crate::{db, error::AppError, song_id::SongId, AppState};
+
+const TOP_ARTISTS: i64 = 10;
+
+const DEFAULT_BUCKETS: [i64; 3] = [1, 10, 100];
+const MAX_BUCKETS: usize = 20;
+
+#[derive(Deserialize)]
+pub struct HistogramParams {
+    buckets: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeseriesParams {
+    bucket: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct PlayPeriod {
+    period: String,
+    count: i64,
+}
+
+#[derive(Serialize)]
+pub struct PlayHistogram {
+    buckets: Vec<HistogramBucket>,
+}
+
+#[derive(Serialize)]
+pub struct HistogramBucket {
+    label: String,
+    min: i64,
+    max: Option<i64>,
+    songs: i64,
+}
+
+// Listening time is duration_seconds * play_count. Songs without a
+// duration count towards plays but not towards either duration total.
+// TOTAL() is used over SUM() so a huge play count cannot overflow.
+#[derive(Serialize)]
+pub struct LibraryStats {
+    songs: i64,
+    total_plays: i64,
+    total_duration_seconds: i64,
+    listening_seconds: i64,
+    top_artists: Vec<ArtistListening>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct ArtistListening {
+    artist: String,
+    listening_seconds: i64,
+}
+
+pub async fn library_stats(State(state): State<AppState>) -> Result<Json<LibraryStats>, AppError> {
+    let (songs, total_plays, total_duration, listening): (i64, f64, f64, f64) = db::timed(
+        state.config.slow_query_ms,
+        "library_stats",
+        sqlx::query_as(
+            "SELECT COUNT(*), TOTAL(play_count), TOTAL(duration_seconds),
+            TOTAL(duration_seconds * play_count) FROM songs",
+        )
+        .fetch_one(&state.db),
+    )
+    .await?;
+
+    let top_artists = db::timed(
+        state.config.slow_query_ms,
+        "artist_listening",
+        sqlx::query_as::<_, ArtistListening>(
+            "SELECT artist, CAST(TOTAL(duration_seconds * play_count) AS INTEGER)
+            AS listening_seconds FROM songs WHERE duration_seconds IS NOT NULL
+            GROUP BY artist COLLATE NOCASE
+            ORDER BY listening_seconds DESC, artist COLLATE NOCASE LIMIT ?",
+        )
+        .bind(TOP_ARTISTS)
+        .fetch_all(&state.db),
+    )
+    .await?;
+
+    Ok(Json(LibraryStats {
+        songs,
+        total_plays: total_plays as i64,
+        total_duration_seconds: total_duration as i64,
+        listening_seconds: listening as i64,
+        top_artists,
+    }))
+}
+
+// Nearest-rank percentiles: pN is the play_count of the song at rank
+// ceil(N * songs / 100) in ascending order, so every value is one some song
+// actually has. All three are null for an empty library.
+#[derive(FromRow, Serialize)]
+pub struct PlayPercentiles {
+    songs: i64,
+    p50: Option<i64>,
+    p90: Option<i64>,
+    p99: Option<i64>,
+}
+
+pub async fn play_percentiles(
+    State(state): State<AppState>,
+) -> Result<Json<PlayPercentiles>, AppError> {
+    let percentiles = db::timed(
+        state.config.slow_query_ms,
+        "play_percentiles",
+        sqlx::query_as::<_, PlayPercentiles>(
+            "WITH ranked AS (
+                SELECT play_count, ROW_NUMBER() OVER (ORDER BY play_count) AS rank,
+                COUNT(*) OVER () AS songs FROM songs
+            )
+            SELECT COUNT(*) AS songs,
+            MAX(CASE WHEN rank = (50 * songs + 99) / 100 THEN play_count END) AS p50,
+            MAX(CASE WHEN rank = (90 * songs + 99) / 100 THEN play_count END) AS p90,
+            MAX(CASE WHEN rank = (99 * songs + 99) / 100 THEN play_count END) AS p99
+            FROM ranked",
+        )
+        .fetch_one(&state.db),
+    )
+    .await?;
+
+    Ok(Json(percentiles))
+}
+
+pub async fn play_histogram(
+    State(state): State<AppState>,
+    Query(params): Query<HistogramParams>,
+) -> Result<Json<PlayHistogram>, AppError> {
+    let boundaries = match params.buckets.as_deref() {
+        Some(raw) => parse_boundaries(raw)?,
+        None => DEFAULT_BUCKETS.to_vec(),
+    };
+
+    let mut builder = QueryBuilder::new("SELECT CASE");
+    for (index, boundary) in boundaries.iter().enumerate() {
+        builder.push(" WHEN play_count < ");
+        builder.push_bind(*boundary);
+        builder.push(format!(" THEN {}", index));
+    }
+    builder.push(format!(
+        " ELSE {} END AS bucket, COUNT(*) FROM songs GROUP BY bucket",
+        boundaries.len()
+    ));
+
+    let counts: Vec<(i64, i64)> = db::timed(
+        state.config.slow_query_ms,
+        "play_histogram",
+        builder.build_query_as().fetch_all(&state.db),
+    )
+    .await?;
+
+    let mut buckets = Vec::with_capacity(boundaries.len() + 1);
+    let mut min = 0;
+    for index in 0..=boundaries.len() {
+        let max = boundaries.get(index).map(|boundary| boundary - 1);
+        let label = match max {
+            Some(max) if max == min => min.to_string(),
+            Some(max) => format!("{}-{}", min, max),
+            None => format!("{}+", min),
+        };
+        let songs = counts
+            .iter()
+            .find(|(bucket, _)| *bucket == index as i64)
+            .map_or(0, |(_, songs)| *songs);
+
+        buckets.push(HistogramBucket {
+            label,
+            min,
+            max,
+            songs,
+        });
+
+        if let Some(max) = max {
+            min = max + 1;
+        }
+    }
+
+    Ok(Json(PlayHistogram { buckets }))
+}
+
+fn parse_boundaries(raw: &str) -> Result<Vec<i64>, AppError> {
+    let boundaries = raw
+        .split(',')
+        .map(|boundary| boundary.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AppError::bad_request("buckets must be a comma-separated list of integers"))?;
+
+    if boundaries.is_empty() || boundaries.len() > MAX_BUCKETS {
+        return Err(AppError::bad_request(format!(
+            "buckets must list between 1 and {} boundaries",
+            MAX_BUCKETS
+        )));
+    }
+
+    if boundaries[0] < 1 || boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(AppError::bad_request(
+            "buckets must be positive and strictly increasing",
+        ));
+    }
+
+    Ok(boundaries)
+}
+
+// Weeks start on Monday and are labelled with that date. Only periods with
+// at least one play are listed.
+pub async fn play_timeseries(
+    State(state): State<AppState>,
+    song_id: SongId,
+    Query(params): Query<TimeseriesParams>,
+) -> Result<Json<Vec<PlayPeriod>>, AppError> {
+    let period = match params.bucket.as_deref().unwrap_or("week") {
+        "day" => "date(played_at)",
+        "week" => "date(played_at, 'weekday 0', '-6 days')",
+        _ => return Err(AppError::bad_request("bucket must be day or week")),
+    };
+
+    let periods = db::timed(
+        state.config.slow_query_ms,
+        "play_timeseries",
+        db::with_tx(&state.db, |tx| {
+            Box::pin(async move {
+                let exists: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM songs WHERE id = ?")
+                    .bind(song_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if exists.is_none() {
+                    return Err(AppError::not_found("Song not found"));
+                }
+
+                let periods = sqlx::query_as::<_, PlayPeriod>(&format!(
+                    "SELECT {} AS period, COUNT(*) AS count FROM plays WHERE song_id = ?
+                    GROUP BY period ORDER BY period",
+                    period
+                ))
+                .bind(song_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                Ok(periods)
+            })
+        }),
+    )
+    .await?;
+
+    Ok(Json(periods))
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::watch;
+use tracing::error;
+
+use crate::{db, error::AppError};
+
+#[derive(Serialize)]
+pub struct RecomputeResult {
+    artists: u64,
+    genres: u64,
+}
+
+// Snapshots of per-artist and per-genre counts for offline reporting; no
+// endpoint reads them, so the periodic task only runs when
+// SUMMARY_INTERVAL_SECS is set and /admin/recompute refreshes them on demand.
+pub async fn recompute(db: &SqlitePool) -> Result<RecomputeResult, AppError> {
+    db::with_write_tx(db, |tx| {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM artist_summaries")
+                .execute(&mut *tx)
+                .await?;
+            let artists = sqlx::query(
+                "INSERT INTO artist_summaries (artist, song_count, total_plays)
+                SELECT artist, COUNT(*), COALESCE(SUM(play_count), 0) FROM songs GROUP BY artist",
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            sqlx::query("DELETE FROM genre_summaries")
+                .execute(&mut *tx)
+                .await?;
+            let genres = sqlx::query(
+                "INSERT INTO genre_summaries (genre, song_count, total_plays)
+                SELECT genre, COUNT(*), COALESCE(SUM(play_count), 0) FROM songs GROUP BY genre",
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            Ok(RecomputeResult { artists, genres })
+        })
+    })
+    .await
+}
+
+pub async fn run_periodic(db: SqlitePool, period: Duration, mut shutdown: watch::Receiver<bool>) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(error) = recompute(&db).await {
+                    error!("Failed to recompute summaries: {}", error);
+                }
+            }
+            _ = shutdown.changed() => break,
+        }
+    }
+}
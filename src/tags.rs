@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+use sqlx::{Executor, Sqlite, SqliteConnection};
+
+use crate::{db, error::AppError, song_id::SongId, text, AppState};
+
+const MAX_TAG_LENGTH: usize = 50;
+
+#[derive(Deserialize)]
+pub struct AddTag {
+    tag: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagPath {
+    tag: String,
+}
+
+pub fn normalize(tag: &str) -> Result<String, AppError> {
+    let tag = text::normalize(tag.trim()).to_lowercase();
+
+    if tag.is_empty() {
+        return Err(AppError::bad_request("tag must not be empty"));
+    }
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "tag must be at most {} characters",
+            MAX_TAG_LENGTH
+        )));
+    }
+
+    Ok(tag)
+}
+
+pub async fn song_tags<'e>(
+    executor: impl Executor<'e, Database = Sqlite>,
+    song_id: SongId,
+) -> Result<Vec<String>, sqlx::Error> {
+    let tags: Vec<(String,)> = sqlx::query_as(
+        "SELECT tags.name FROM song_tags JOIN tags ON tags.id = song_tags.tag_id
+        WHERE song_tags.song_id = ? ORDER BY tags.name",
+    )
+    .bind(song_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(tags.into_iter().map(|(name,)| name).collect())
+}
+
+// Tagging bumps the song's updated_at so conditional search responses,
+// which can now filter on tags, are not served stale.
+async fn touch_song(conn: &mut SqliteConnection, song_id: SongId) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "UPDATE songs SET updated_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') WHERE id = ?",
+    )
+    .bind(song_id)
+    .execute(conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found("Song not found"));
+    }
+
+    Ok(())
+}
+
+pub async fn add_tag(
+    State(state): State<AppState>,
+    song_id: SongId,
+    Json(payload): Json<AddTag>,
+) -> Result<Json<Vec<String>>, AppError> {
+    let tag = normalize(&payload.tag)?;
+
+    let tags = db::timed(
+        state.config.slow_query_ms,
+        "add_tag",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                touch_song(&mut *tx, song_id).await?;
+
+                sqlx::query("INSERT INTO tags (name) VALUES (?) ON CONFLICT (name) DO NOTHING")
+                    .bind(&tag)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query(
+                    "INSERT OR IGNORE INTO song_tags (song_id, tag_id)
+                    SELECT ?, id FROM tags WHERE name = ?",
+                )
+                .bind(song_id)
+                .bind(&tag)
+                .execute(&mut *tx)
+                .await?;
+
+                Ok(song_tags(&mut *tx, song_id).await?)
+            })
+        }),
+    )
+    .await?;
+
+    Ok(Json(tags))
+}
+
+pub async fn remove_tag(
+    State(state): State<AppState>,
+    song_id: SongId,
+    Path(path): Path<TagPath>,
+) -> Result<Json<Vec<String>>, AppError> {
+    let tag = normalize(&path.tag)?;
+
+    let tags = db::timed(
+        state.config.slow_query_ms,
+        "remove_tag",
+        db::with_write_tx(&state.db, |tx| {
+            Box::pin(async move {
+                touch_song(&mut *tx, song_id).await?;
+
+                let result = sqlx::query(
+                    "DELETE FROM song_tags WHERE song_id = ?
+                    AND tag_id = (SELECT id FROM tags WHERE name = ?)",
+                )
+                .bind(song_id)
+                .bind(&tag)
+                .execute(&mut *tx)
+                .await?;
+
+                if result.rows_affected() == 0 {
+                    return Err(AppError::not_found("Song does not have this tag"));
+                }
+
+                Ok(song_tags(&mut *tx, song_id).await?)
+            })
+        }),
+    )
+    .await?;
+
+    Ok(Json(tags))
+}
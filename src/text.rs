@@ -0,0 +1,21 @@
+use unicode_normalization::UnicodeNormalization;
+
+// Stored values and search terms are both put in NFC so a composed "é"
+// and an "e" followed by a combining accent compare equal.
+pub fn normalize(value: &str) -> String {
+    value.nfc().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composed_and_decomposed_forms_normalize_equal() {
+        let composed = "Beyonc\u{e9}";
+        let decomposed = "Beyonce\u{301}";
+
+        assert_ne!(composed, decomposed);
+        assert_eq!(normalize(composed), normalize(decomposed));
+    }
+}
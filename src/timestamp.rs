@@ -0,0 +1,82 @@
+use chrono::{DateTime, Duration, NaiveDate, SubsecRound, Utc};
+
+use crate::error::AppError;
+
+// Matches the strftime('%Y-%m-%dT%H:%M:%SZ', 'now') format the songs table
+// stores, so formatted values compare correctly as text.
+const DB_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+// The start is inclusive and the end exclusive, so callers compare with
+// >= and <.
+pub type Window = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+pub fn parse(name: &str, value: &str) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    parse_date(name, value)
+}
+
+fn parse_date(name: &str, value: &str) -> Result<DateTime<Utc>, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+        .ok_or_else(|| {
+            AppError::bad_request(format!("{} must be an ISO 8601 date or timestamp", name))
+        })
+}
+
+// A date-only end covers that whole day, and a timestamp covers its whole
+// second, since stored timestamps have no fractional part.
+fn parse_end(name: &str, value: &str) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc).trunc_subsecs(0) + Duration::seconds(1));
+    }
+
+    Ok(parse_date(name, value)? + Duration::days(1))
+}
+
+pub fn parse_window(from: Option<&str>, to: Option<&str>) -> Result<Window, AppError> {
+    let from = from.map(|from| parse("from", from)).transpose()?;
+    let to = to.map(|to| parse_end("to", to)).transpose()?;
+
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return Err(AppError::bad_request("from must not be after to"));
+        }
+    }
+
+    Ok((from, to))
+}
+
+pub fn to_db(timestamp: DateTime<Utc>) -> String {
+    timestamp.format(DB_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_only_to_covers_the_whole_day() {
+        let (from, to) = parse_window(Some("2024-11-29"), Some("2024-11-29")).unwrap();
+
+        assert_eq!(to_db(from.unwrap()), "2024-11-29T00:00:00Z");
+        assert_eq!(to_db(to.unwrap()), "2024-11-30T00:00:00Z");
+    }
+
+    #[test]
+    fn timestamp_to_covers_its_second() {
+        let (_, to) = parse_window(None, Some("2024-11-29T10:15:30.250Z")).unwrap();
+
+        assert_eq!(to_db(to.unwrap()), "2024-11-29T10:15:31Z");
+    }
+
+    #[test]
+    fn from_after_to_is_rejected() {
+        assert!(parse_window(Some("2024-11-30"), Some("2024-11-29")).is_err());
+        assert!(parse_window(Some("2024-11-29T10:00:00Z"), Some("2024-11-29T10:00:00Z")).is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, HeaderName, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+const ACCEPT_VERSION: HeaderName = HeaderName::from_static("accept-version");
+const API_VERSION: HeaderName = HeaderName::from_static("x-api-version");
+
+// v1 list endpoints return a bare array; v2 wraps them in PagedSongs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    fn parse(value: &str) -> Result<Self, AppError> {
+        match value.trim().trim_start_matches(['v', 'V']) {
+            "1" => Ok(ApiVersion::V1),
+            "2" => Ok(ApiVersion::V2),
+            _ => Err(AppError::bad_request(format!(
+                "Unsupported API version: {}; supported versions are 1 and 2",
+                value
+            ))),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "1",
+            ApiVersion::V2 => "2",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VersionQuery {
+    api_version: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(value) = parts.headers.get(ACCEPT_VERSION) {
+            let value = value
+                .to_str()
+                .map_err(|_| AppError::bad_request("Accept-Version is not valid text"))?;
+            return Self::parse(value);
+        }
+
+        let query = Query::<VersionQuery>::try_from_uri(&parts.uri).ok();
+        match query.and_then(|Query(query)| query.api_version) {
+            Some(value) => Self::parse(&value),
+            None => Ok(Self::LATEST),
+        }
+    }
+}
+
+impl IntoResponseParts for ApiVersion {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut()
+            .insert(API_VERSION, HeaderValue::from_static(self.as_str()));
+        Ok(res)
+    }
+}